@@ -1,10 +1,130 @@
-use std::{ffi::OsString, error::Error, vec, os::unix::prelude::OsStringExt};
+use std::{ffi::OsString, error::Error, vec, os::unix::prelude::OsStringExt, collections::VecDeque};
+
+use crate::debugger::memory_map::{self, Region};
+use crate::interrupt::{IrqLine, NmiLine};
+use crate::joypad::{Joypad, Peripheral};
+#[cfg(feature = "ppu")]
+use crate::ppu::PPU;
+
+// A write to a sound register, timestamped with the bus cycle it happened on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterWrite
+{
+    pub cycle: u64,
+    pub addr: u16,
+    pub val: u8
+}
+
+// What the CPU needs from the memory it is connected to. Implement it to run
+// the CPU against something other than the NES bus, e.g. a flat 64K RAM for
+// another 6502 machine or a test double.
+pub trait BusInterface
+{
+    fn read8(&mut self, addr: u16) -> u8;
+
+    fn write8(&mut self, addr: u16, val: u8);
+
+    // Reads a byte without side effects on the devices, for debugging tools
+    fn peek8(&self, addr: u16) -> u8;
+
+    fn read16(&mut self, addr: u16) -> u16
+    {
+        let l = self.read8(addr) as u16;
+        let h = self.read8(addr.wrapping_add(1)) as u16;
+        l | (h << 8)
+    }
+
+    // Clocks the devices on the bus by one CPU cycle
+    fn tick(&mut self) {}
+
+    // Returns the first unmapped address accessed since the last call
+    fn take_fault(&mut self) -> Option<u16>
+    {
+        None
+    }
+
+    // Records the previous value of every byte written while enabled, which
+    // the CPU journal uses to undo writes. Buses that don't support it can't
+    // restore memory on step_back().
+    fn set_write_log(&mut self, _enabled: bool) {}
+
+    // Moves the logged writes to the end of `into`. The log keeps its buffer,
+    // so a running journal doesn't allocate on every instruction.
+    fn drain_write_log(&mut self, _into: &mut Vec<(u16, u8)>) {}
+
+    // Records CPU accesses to the given addresses or any of their mirrors, for
+    // read and write breakpoints. An empty list stops watching.
+    fn set_watchpoints(&mut self, _addrs: &[u16]) {}
+
+    fn take_watch_hits(&mut self) -> Vec<Access>
+    {
+        Vec::new()
+    }
+
+    // Polled by the CPU between instructions
+    fn is_irq_asserted(&self) -> bool
+    {
+        false
+    }
+
+    // Acknowledges a pending NMI, returns whether there was one
+    fn take_nmi(&mut self) -> bool
+    {
+        false
+    }
+
+    // Whether an NMI is pending or can still be raised, e.g. the PPU has NMIs
+    // enabled. Tells a wait-for-NMI loop apart from a crashed program.
+    fn is_nmi_enabled(&self) -> bool
+    {
+        false
+    }
+}
+
+// A CPU access captured by a device trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access
+{
+    pub cycle: u64,
+    pub addr: u16,
+    pub val: u8,
+    pub write: bool
+}
+
+// Ring buffer of the last accesses to one region
+struct AccessTrace
+{
+    region: Region,
+    capacity: usize,
+    entries: VecDeque<Access>
+}
 
 pub struct Bus
 {
     ram: Vec<u8>,
-    ppu: Vec<u8>,
-    apu: Vec<u8>
+    #[cfg(feature = "ppu")]
+    ppu: PPU,
+    apu: Vec<u8>,
+    test: Vec<u8>,
+    test_mode: bool,
+    fault: Option<u16>,
+    write_log: Option<Vec<(u16, u8)>>,
+    audio_log: Option<Vec<RegisterWrite>>,
+    access_traces: Vec<AccessTrace>,
+    watchpoints: Vec<u16>,
+    watch_hits: Vec<Access>,
+    cycle: u64,
+    irq: IrqLine,
+    nmi: NmiLine,
+    ports: [Peripheral; 2]
+}
+
+impl Default for Bus
+{
+    fn default() -> Self
+    {
+        Bus::new()
+    }
 }
 
 impl Bus
@@ -13,13 +133,234 @@ impl Bus
     {
         Bus {
             ram: vec![0; 0x800],
-            ppu: vec![0; 8],
-            apu: vec![0; 18]
+            #[cfg(feature = "ppu")]
+            ppu: PPU::new(),
+            apu: vec![0; 0x18],
+            test: vec![0; 8],
+            test_mode: false,
+            fault: None,
+            write_log: None,
+            audio_log: None,
+            access_traces: Vec::new(),
+            watchpoints: Vec::new(),
+            watch_hits: Vec::new(),
+            cycle: 0,
+            irq: IrqLine::new(),
+            nmi: NmiLine::new(),
+            ports: [Peripheral::Joypad(Joypad::new()); 2]
         }        
     }
 
+    // Retail consoles have the test registers disabled and the range reads as open bus
+    pub fn set_test_mode(&mut self, enabled: bool)
+    {
+        self.test_mode = enabled;
+    }
+
+    pub fn is_test_mode(&self) -> bool
+    {
+        self.test_mode
+    }
+
+    // Clocks the devices on the bus by one CPU cycle
+    pub fn tick(&mut self)
+    {
+        #[cfg(feature = "ppu")]
+        {
+            self.ppu.tick();
+            self.nmi.set(self.ppu.is_nmi_asserted());
+        }
+        self.cycle += 1;
+    }
+
+    pub fn get_cycle(&self) -> u64
+    {
+        self.cycle
+    }
+
+    // Returns the first unmapped address accessed since the last call
+    pub fn take_fault(&mut self) -> Option<u16>
+    {
+        self.fault.take()
+    }
+
+    fn raise_fault(&mut self, addr: u16)
+    {
+        if self.fault.is_none() {
+            self.fault = Some(addr);
+        }
+    }
+
+    // Records the previous value of every RAM byte written while enabled
+    pub fn set_write_log(&mut self, enabled: bool)
+    {
+        self.write_log = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    pub fn drain_write_log(&mut self, into: &mut Vec<(u16, u8)>)
+    {
+        if let Some(log) = self.write_log.as_mut() {
+            into.append(log);
+        }
+    }
+
+    // Records every write to the APU sound registers while enabled, for
+    // music ripping and offline analysis
+    pub fn set_audio_log(&mut self, enabled: bool)
+    {
+        self.audio_log = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    pub fn take_audio_log(&mut self) -> Vec<RegisterWrite>
+    {
+        self.audio_log.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    // Captures the last `capacity` CPU accesses to a region, e.g. only PPU
+    // register traffic. A capacity of 0 stops tracing the region.
+    pub fn set_access_trace(&mut self, region: Region, capacity: usize)
+    {
+        self.access_traces.retain(|trace| trace.region != region);
+        if capacity > 0 {
+            self.access_traces.push(AccessTrace { region, capacity, entries: VecDeque::with_capacity(capacity) });
+        }
+    }
+
+    pub fn take_access_trace(&mut self, region: Region) -> Vec<Access>
+    {
+        self.access_traces.iter_mut()
+            .find(|trace| trace.region == region)
+            .map(|trace| trace.entries.drain(..).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn set_watchpoints(&mut self, addrs: &[u16])
+    {
+        self.watchpoints = addrs.iter().map(|addr| memory_map::canonicalize(*addr)).collect();
+        self.watch_hits.clear();
+    }
+
+    pub fn take_watch_hits(&mut self) -> Vec<Access>
+    {
+        std::mem::take(&mut self.watch_hits)
+    }
+
+    fn is_access_hooked(&self) -> bool
+    {
+        !self.access_traces.is_empty() || !self.watchpoints.is_empty()
+    }
+
+    fn trace_access(&mut self, addr: u16, val: u8, write: bool)
+    {
+        if self.watchpoints.contains(&memory_map::canonicalize(addr)) {
+            self.watch_hits.push(Access { cycle: self.cycle, addr, val, write });
+        }
+
+        let region = Region::of(addr);
+        if let Some(trace) = self.access_traces.iter_mut().find(|trace| trace.region == region) {
+            if trace.entries.len() == trace.capacity {
+                trace.entries.pop_front();
+            }
+            trace.entries.push_back(Access { cycle: self.cycle, addr, val, write });
+        }
+    }
+
+    // Devices and mappers assert their IRQ source here
+    pub fn get_irq_line(&mut self) -> &mut IrqLine
+    {
+        &mut self.irq
+    }
+
+    pub fn get_nmi_line(&mut self) -> &mut NmiLine
+    {
+        &mut self.nmi
+    }
+
+    // Port 0 is read at $4016, port 1 at $4017. Both have a standard
+    // controller connected by default.
+    pub fn connect(&mut self, port: usize, peripheral: Peripheral)
+    {
+        self.ports[port] = peripheral;
+    }
+
+    pub fn get_peripheral(&mut self, port: usize) -> &mut Peripheral
+    {
+        &mut self.ports[port]
+    }
+
+    // Returns the controller in the port, if it is one
+    pub fn get_joypad(&mut self, port: usize) -> Option<&mut Joypad>
+    {
+        match &mut self.ports[port] {
+            Peripheral::Joypad(pad) => Some(pad),
+            _ => None
+        }
+    }
+
+    #[cfg(feature = "ppu")]
+    pub fn get_ppu(&mut self) -> &mut PPU
+    {
+        &mut self.ppu
+    }
+
+    pub fn dump_cpu_ram(&self) -> Vec<u8>
+    {
+        self.ram.clone()
+    }
+
+    pub fn load_cpu_ram(&mut self, data: &[u8]) -> Result<(), Box<dyn Error>>
+    {
+        if data.len() != self.ram.len() {
+            return Err(format!("CPU RAM image must be {} bytes, got {}", self.ram.len(), data.len()).into());
+        }
+
+        self.ram.copy_from_slice(data);
+        Ok(())
+    }
+
+    // Reads a byte without side effects on the devices, for debugging tools
+    pub fn peek8(&self, addr: u16) -> u8
+    {
+        let addr = addr as usize;
+        match addr {
+            0x0000..=0x1FFF => self.ram[addr % 0x800],
+            #[cfg(feature = "ppu")]
+            0x2000..=0x3FFF => self.ppu.peek_register((addr - 0x2000) % 8),
+            0x4016 | 0x4017 => self.ports[addr - 0x4016].peek() | Bus::joypad_open_bus(addr),
+            0x4000..=0x4015 => self.apu[addr - 0x4000],
+            0x4018..=0x401F if self.test_mode => self.test[addr - 0x4018],
+            _ => (addr >> 8) as u8
+        }
+    }
+
+    // Only the low bits are driven by the controller ports, the rest keep the
+    // high byte of the address left on the data bus
+    fn joypad_open_bus(addr: usize) -> u8
+    {
+        (addr >> 8) as u8 & 0xE0
+    }
+
     #[inline(always)]
-    pub fn read8(&self, addr: u16) -> u8
+    pub fn read8(&mut self, addr: u16) -> u8
+    {
+        let val = self.read_device(addr);
+        if self.is_access_hooked() {
+            self.trace_access(addr, val, false);
+        }
+        val
+    }
+
+    #[inline(always)]
+    pub fn write8(&mut self, addr: u16, val: u8)
+    {
+        if self.is_access_hooked() {
+            self.trace_access(addr, val, true);
+        }
+        self.write_device(addr, val);
+    }
+
+    #[inline(always)]
+    fn read_device(&mut self, addr: u16) -> u8
     {
         let addr = addr as usize;
 
@@ -29,73 +370,107 @@ impl Bus
             return self.ram[addr];
         }
 
-        // PPU
-        if addr >= 0x2000 && addr < 0x4000 {
-            let addr = (addr - 0x2000) % 8;
-            return self.ppu[addr];
+        // PPU, open bus when compiled out
+        if (0x2000..0x4000).contains(&addr) {
+            #[cfg(feature = "ppu")]
+            return self.ppu.read_register((addr - 0x2000) % 8);
+            #[cfg(not(feature = "ppu"))]
+            return (addr >> 8) as u8;
+        }
+
+        // Controllers
+        if addr == 0x4016 || addr == 0x4017 {
+            return self.ports[addr - 0x4016].read() | Bus::joypad_open_bus(addr);
         }
 
         // APU & I/O
-        if addr >= 0x4000 && addr < 0x4018 {
+        if (0x4000..0x4018).contains(&addr) {
             return self.apu[addr - 0x4000]
         }
 
         // CPU Test Mode
-        if addr >= 0x4018 && addr < 0x4020 {
-            todo!("CPU Test Mode is not implemented")
+        if (0x4018..0x4020).contains(&addr) {
+            if self.test_mode {
+                return self.test[addr - 0x4018];
+            }
+
+            // Nothing drives the data bus, so the last fetched byte is read back.
+            // For absolute addressing that is the high byte of the address.
+            return (addr >> 8) as u8;
         }
 
         // Cartridge space
         if addr >= 0x4020 {
-            todo!("Cartrige is not implemented")
+            // No cartridge is connected, so the data bus is left floating
+            self.raise_fault(addr as u16);
+            return (addr >> 8) as u8;
         }
 
         panic!("Invalud address: {}", addr)
     }
 
     #[inline(always)]
-    pub fn write8(&mut self, addr: u16, val: u8)
+    fn write_device(&mut self, addr: u16, val: u8)
     {
         let addr = addr as usize;
 
         // RAM
         if addr < 0x2000 {
             let addr = addr % 0x800;
+            if let Some(log) = &mut self.write_log {
+                log.push((addr as u16, self.ram[addr]));
+            }
+
             self.ram[addr] = val;
             return;
         }
 
         // PPU
-        if addr >= 0x2000 && addr < 0x4000 {
-            let addr = (addr - 0x2000) % 8;
-            self.ppu[addr] = val;           
+        if (0x2000..0x4000).contains(&addr) {
+            #[cfg(feature = "ppu")]
+            self.ppu.write_register((addr - 0x2000) % 8, val);
             return;
         }
 
         // APU & I/O
-        if addr >= 0x4000 && addr < 0x4018 {
+        if (0x4000..0x4018).contains(&addr) {
+            // $4014 is OAM DMA and $4016 the controller strobe, the rest is sound
+            if let Some(log) = &mut self.audio_log {
+                if addr != 0x4014 && addr != 0x4016 {
+                    log.push(RegisterWrite { cycle: self.cycle, addr: addr as u16, val });
+                }
+            }
+
+            if addr == 0x4016 {
+                self.ports.iter_mut().for_each(|port| port.write(val));
+            }
+
             self.apu[addr - 0x4000] = val;
             return;
         }
 
         // CPU Test Mode
-        if addr >= 0x4018 && addr < 0x4020 {
-            todo!("CPU Test Mode is not implemented")
+        if (0x4018..0x4020).contains(&addr) {
+            if self.test_mode {
+                self.test[addr - 0x4018] = val;
+            }
+            return;
         }
 
         // Cartridge space
         if addr >= 0x4020 {
-            todo!("Cartrige is not implemented")
+            self.raise_fault(addr as u16);
+            return;
         }
 
         panic!("Invalud address: {}", addr)
     }
 
     #[inline(always)]
-    pub fn read16(&self, addr: u16) -> u16 
+    pub fn read16(&mut self, addr: u16) -> u16 
     {
         let l:u16 = self.read8(addr) as u16;
-        let h:u16 = self.read8(addr + 1) as u16;
+        let h:u16 = self.read8(addr.wrapping_add(1)) as u16;
         l | (h << 8)
     }
 
@@ -103,11 +478,11 @@ impl Bus
     pub fn write16(&mut self, addr: u16, val: u16)
     {
         self.write8(addr, (val & 0xFF) as u8);
-        self.write8(addr + 1, (val >> 8) as u8);
+        self.write8(addr.wrapping_add(1), (val >> 8) as u8);
     }
 
     #[inline(always)]
-    pub fn read_str(&self, addr: u16) -> Result<String, Box<dyn Error>>
+    pub fn read_str(&mut self, addr: u16) -> Result<String, Box<dyn Error>>
     {
         let mut buf = Vec::<u8>::new();
         let mut offset: usize = 0;
@@ -134,18 +509,147 @@ impl Bus
     #[inline(always)]
     pub fn write_buffer(&mut self, addr: u16, buffer: &[u8])
     {        
-        for i in 0..buffer.len() {
-            let addr = addr + i as u16;
-            self.write8(addr, buffer[i])
+        for (i, byte) in buffer.iter().enumerate() {
+            let addr = addr.wrapping_add(i as u16);
+            self.write8(addr, *byte)
         }
     }
 
     #[inline(always)]
-    pub fn read_buffer(&self, addr: u16, out_buffer: &mut Vec<u8>)
+    pub fn read_buffer(&mut self, addr: u16, out_buffer: &mut [u8])
     {
-        for i in 0..out_buffer.len() {
-            let addr = addr + i as u16;
-            out_buffer[i] = self.read8(addr);
+        for (i, byte) in out_buffer.iter_mut().enumerate() {
+            let addr = addr.wrapping_add(i as u16);
+            *byte = self.read8(addr);
+        }
+    }
+}
+
+impl BusInterface for Bus
+{
+    fn read8(&mut self, addr: u16) -> u8
+    {
+        Bus::read8(self, addr)
+    }
+
+    fn write8(&mut self, addr: u16, val: u8)
+    {
+        Bus::write8(self, addr, val)
+    }
+
+    fn peek8(&self, addr: u16) -> u8
+    {
+        Bus::peek8(self, addr)
+    }
+
+    fn read16(&mut self, addr: u16) -> u16
+    {
+        Bus::read16(self, addr)
+    }
+
+    fn tick(&mut self)
+    {
+        Bus::tick(self)
+    }
+
+    fn take_fault(&mut self) -> Option<u16>
+    {
+        Bus::take_fault(self)
+    }
+
+    fn set_write_log(&mut self, enabled: bool)
+    {
+        Bus::set_write_log(self, enabled)
+    }
+
+    fn drain_write_log(&mut self, into: &mut Vec<(u16, u8)>)
+    {
+        Bus::drain_write_log(self, into)
+    }
+
+    fn set_watchpoints(&mut self, addrs: &[u16])
+    {
+        Bus::set_watchpoints(self, addrs)
+    }
+
+    fn take_watch_hits(&mut self) -> Vec<Access>
+    {
+        Bus::take_watch_hits(self)
+    }
+
+    fn is_irq_asserted(&self) -> bool
+    {
+        self.irq.is_asserted()
+    }
+
+    fn take_nmi(&mut self) -> bool
+    {
+        self.nmi.take()
+    }
+
+    fn is_nmi_enabled(&self) -> bool
+    {
+        #[cfg(feature = "ppu")]
+        if self.ppu.is_nmi_enabled() {
+            return true;
+        }
+
+        self.nmi.is_pending()
+    }
+}
+
+// Flat 64K RAM without any NES devices, for tests that run the CPU on its own.
+// The interrupt lines are plain flags the test sets directly.
+#[cfg(test)]
+pub(crate) mod flat
+{
+    use super::BusInterface;
+
+    pub struct Flat
+    {
+        pub mem: Vec<u8>,
+        pub irq: bool,
+        pub nmi: bool
+    }
+
+    impl Flat
+    {
+        pub fn new(mem: Vec<u8>) -> Flat
+        {
+            Flat { mem, irq: false, nmi: false }
+        }
+    }
+
+    impl BusInterface for Flat
+    {
+        fn read8(&mut self, addr: u16) -> u8
+        {
+            self.mem[addr as usize]
+        }
+
+        fn write8(&mut self, addr: u16, val: u8)
+        {
+            self.mem[addr as usize] = val;
+        }
+
+        fn peek8(&self, addr: u16) -> u8
+        {
+            self.mem[addr as usize]
+        }
+
+        fn is_irq_asserted(&self) -> bool
+        {
+            self.irq
+        }
+
+        fn take_nmi(&mut self) -> bool
+        {
+            std::mem::take(&mut self.nmi)
+        }
+
+        fn is_nmi_enabled(&self) -> bool
+        {
+            self.nmi
         }
     }
 }
@@ -155,7 +659,8 @@ mod tests
 {
     use std::ffi::CString;
 
-    use super::Bus;
+    use super::{Access, Bus, BusInterface, Peripheral, RegisterWrite, flat::Flat};
+    use crate::debugger::memory_map::Region;
 
     #[test]
     fn read8()
@@ -177,13 +682,14 @@ mod tests
         assert_eq!(42, mem.read8(0x100 + 0x1800));
     }
 
+    #[cfg(feature = "ppu")]
     #[test]
     fn read8_ppu_mirror()
     {
         let mut mem = Bus::new();
-        mem.write8(0x2002, 42);
+        mem.write8(0x2004, 42);
 
-        let mut addr = 0x2002;
+        let mut addr = 0x2004;
         while addr < 0x4000 {
             assert_eq!(42, mem.read8(addr));
             addr += 8;
@@ -223,7 +729,7 @@ mod tests
     fn write_buffer()
     {
         let mut mem = Bus::new();
-        mem.write_buffer(0x600, &vec![0x01, 0x02, 0x03]);
+        mem.write_buffer(0x600, &[0x01, 0x02, 0x03]);
         
         let mut out = vec![0; 3];
         mem.read_buffer(0x600, &mut out);
@@ -238,4 +744,235 @@ mod tests
         mem.read_buffer(0x600 + 0x1800, &mut out);
         assert_eq!(vec![0x01, 0x02, 0x03], out);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_mode_disabled_open_bus()
+    {
+        let mut mem = Bus::new();
+        mem.write8(0x401A, 42);
+
+        assert_eq!(0x40, mem.read8(0x4018));
+        assert_eq!(0x40, mem.read8(0x401A));
+        assert_eq!(0x40, mem.read8(0x401F));
+    }
+
+    #[test]
+    fn test_mode_enabled()
+    {
+        let mut mem = Bus::new();
+        mem.set_test_mode(true);
+        mem.write8(0x401A, 42);
+
+        assert_eq!(42, mem.read8(0x401A));
+        assert_eq!(0, mem.read8(0x401B));
+    }
+
+    #[test]
+    fn cartridge_space_fault()
+    {
+        let mut mem = Bus::new();
+        assert_eq!(None, mem.take_fault());
+
+        assert_eq!(0x80, mem.read8(0x8000));
+        mem.write8(0x6000, 42);
+
+        assert_eq!(Some(0x8000), mem.take_fault());
+        assert_eq!(None, mem.take_fault());
+    }
+
+    #[test]
+    fn dump_cpu_ram()
+    {
+        let mut mem = Bus::new();
+        mem.write8(0x7FF, 42);
+
+        let ram = mem.dump_cpu_ram();
+        assert_eq!(0x800, ram.len());
+        assert_eq!(42, ram[0x7FF]);
+    }
+
+    #[test]
+    fn load_cpu_ram()
+    {
+        let mut mem = Bus::new();
+        let mut ram = vec![0; 0x800];
+        ram[0x100] = 42;
+        mem.load_cpu_ram(&ram).unwrap();
+
+        assert_eq!(42, mem.read8(0x100));
+        assert_eq!(42, mem.read8(0x900));
+        assert!(mem.load_cpu_ram(&ram[..0x100]).is_err());
+    }
+
+    #[test]
+    fn peek8()
+    {
+        let mut mem = Bus::new();
+        mem.write8(0x100, 42);
+        assert_eq!(42, mem.peek8(0x900));
+
+        #[cfg(feature = "ppu")]
+        {
+            mem.get_ppu().set_vblank(true);
+            assert_eq!(0x80, mem.peek8(0x2002) & 0x80);
+            assert_eq!(0x80, mem.peek8(0x2002) & 0x80);
+        }
+        assert_eq!(0x80, mem.peek8(0x8000));
+        assert_eq!(None, mem.take_fault());
+    }
+
+    #[test]
+    fn write_log()
+    {
+        let mut mem = Bus::new();
+        mem.write8(0x100, 1);
+        mem.set_write_log(true);
+        mem.write8(0x900, 2);
+        mem.write8(0x101, 3);
+
+        let mut writes = vec![(0x200, 5)];
+        mem.drain_write_log(&mut writes);
+        assert_eq!(vec![(0x200, 5), (0x100, 1), (0x101, 0)], writes);
+
+        writes.clear();
+        mem.drain_write_log(&mut writes);
+        assert!(writes.is_empty());
+
+        mem.set_write_log(false);
+        mem.write8(0x100, 4);
+        mem.drain_write_log(&mut writes);
+        assert!(writes.is_empty());
+    }
+
+    #[test]
+    fn audio_log()
+    {
+        let mut mem = Bus::new();
+        mem.write8(0x4000, 1);
+        mem.set_audio_log(true);
+        mem.tick();
+        mem.write8(0x4015, 0x0F);
+        mem.write8(0x4014, 0x02);
+        mem.write8(0x4016, 0x01);
+        mem.tick();
+        mem.write8(0x4017, 0x40);
+
+        assert_eq!(vec![
+            RegisterWrite { cycle: 1, addr: 0x4015, val: 0x0F },
+            RegisterWrite { cycle: 2, addr: 0x4017, val: 0x40 }
+        ], mem.take_audio_log());
+        assert!(mem.take_audio_log().is_empty());
+    }
+
+    #[test]
+    fn default_read16()
+    {
+        let mut mem = Flat::new(vec![0; 0x10000]);
+        mem.write8(0xFFFF, 0x34);
+        mem.write8(0x0000, 0x12);
+
+        assert_eq!(0x1234, mem.read16(0xFFFF));
+        assert_eq!(None, mem.take_fault());
+    }
+
+    #[cfg(feature = "ppu")]
+    #[test]
+    fn ppu_nmi()
+    {
+        let mut mem = Bus::new();
+        mem.get_ppu().set_warm_up(false);
+        mem.write8(0x2000, 0x80);
+        mem.get_ppu().set_vblank(true);
+        assert!(!BusInterface::take_nmi(&mut mem));
+
+        mem.tick();
+        assert!(BusInterface::take_nmi(&mut mem));
+        assert!(!BusInterface::take_nmi(&mut mem));
+    }
+
+    #[test]
+    fn irq_line()
+    {
+        let mut mem = Bus::new();
+        mem.get_irq_line().set(crate::interrupt::irq_source::MAPPER, true);
+
+        assert!(BusInterface::is_irq_asserted(&mem));
+    }
+
+    #[test]
+    fn joypad_ports()
+    {
+        let mut mem = Bus::new();
+        mem.get_joypad(0).unwrap().set_buttons(crate::joypad::button::A);
+        mem.get_joypad(1).unwrap().set_buttons(crate::joypad::button::B);
+        mem.write8(0x4016, 1);
+        mem.write8(0x4016, 0);
+
+        assert_eq!(0x41, mem.peek8(0x4016));
+        assert_eq!(0x41, mem.read8(0x4016));
+        assert_eq!(0x40, mem.read8(0x4016));
+        assert_eq!(0x40, mem.read8(0x4017));
+        assert_eq!(0x41, mem.read8(0x4017));
+    }
+
+    #[test]
+    fn power_pad_port()
+    {
+        let mut mem = Bus::new();
+        let mut pad = crate::joypad::PowerPad::new();
+        pad.set_pressed(2, true);
+        mem.connect(1, Peripheral::PowerPad(pad));
+        mem.connect(0, Peripheral::None);
+        mem.write8(0x4016, 1);
+        mem.write8(0x4016, 0);
+
+        assert!(mem.get_joypad(1).is_none());
+        assert_eq!(0x40, mem.read8(0x4016));
+        assert_eq!(0x48, mem.read8(0x4017));
+    }
+
+    #[test]
+    fn access_trace()
+    {
+        let mut mem = Bus::new();
+        mem.set_access_trace(Region::ApuIo, 2);
+        mem.write8(0x4000, 0x21);
+        mem.write8(0x0010, 0x01);
+        mem.tick();
+        mem.write8(0x4015, 0x00);
+        mem.read8(0x4000);
+
+        assert_eq!(vec![
+            Access { cycle: 1, addr: 0x4015, val: 0x00, write: true },
+            Access { cycle: 1, addr: 0x4000, val: 0x21, write: false }
+        ], mem.take_access_trace(Region::ApuIo));
+        assert!(mem.take_access_trace(Region::ApuIo).is_empty());
+        assert!(mem.take_access_trace(Region::Ram).is_empty());
+
+        mem.set_access_trace(Region::ApuIo, 0);
+        mem.write8(0x4000, 0x21);
+        assert!(mem.take_access_trace(Region::ApuIo).is_empty());
+    }
+
+    #[test]
+    fn watchpoints()
+    {
+        let mut mem = Bus::new();
+        mem.set_watchpoints(&[0x0800, 0x4015]);
+        mem.write8(0x1800, 0x42);
+        mem.read8(0x0001);
+        mem.tick();
+        mem.read8(0x0000);
+        mem.write8(0x4015, 0x0F);
+
+        assert_eq!(vec![
+            Access { cycle: 0, addr: 0x1800, val: 0x42, write: true },
+            Access { cycle: 1, addr: 0x0000, val: 0x42, write: false },
+            Access { cycle: 1, addr: 0x4015, val: 0x0F, write: true }
+        ], mem.take_watch_hits());
+
+        mem.set_watchpoints(&[]);
+        mem.read8(0x0000);
+        assert!(mem.take_watch_hits().is_empty());
+    }
+}
@@ -0,0 +1,332 @@
+use std::error::Error;
+
+const SIZE: usize = 0x100;
+
+// Control byte of the 24C02, with A2-A1-A0 tied low as on the Bandai FCG boards
+const DEVICE_ADDRESS: u8 = 0xA0;
+const READ: u8 = 0x01;
+
+// Writes wrap around within an 8 byte page
+const PAGE_MASK: u8 = 0x07;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase
+{
+    Idle,
+    Device,
+    Address,
+    Write,
+    Read
+}
+
+// 256 byte I2C serial EEPROM used for saves by the Bandai LZ93D50 boards of
+// mapper 16. The mapper drives SCL and SDA from a register and reads back the
+// EEPROM's SDA output.
+#[derive(Debug, Clone)]
+pub struct Eeprom24C02
+{
+    data: [u8; SIZE],
+    scl: bool,
+    sda: bool,
+    output: bool,
+    phase: Phase,
+    next: Phase,
+    shift: u8,
+    bit: u8,
+    addr: u8,
+    read_ack: bool
+}
+
+impl Default for Eeprom24C02
+{
+    fn default() -> Self
+    {
+        Eeprom24C02::new()
+    }
+}
+
+impl Eeprom24C02
+{
+    pub fn new() -> Eeprom24C02
+    {
+        Eeprom24C02 {
+            // Erased cells read as 1s
+            data: [0xFF; SIZE],
+            scl: true,
+            sda: true,
+            output: true,
+            phase: Phase::Idle,
+            next: Phase::Idle,
+            shift: 0,
+            bit: 0,
+            addr: 0,
+            read_ack: false
+        }
+    }
+
+    // Sets the lines driven by the mapper. Data is sampled on the rising edge
+    // of SCL and shifted out on the falling edge, changing SDA while SCL is
+    // high signals start (falling) or stop (rising).
+    pub fn set_lines(&mut self, scl: bool, sda: bool)
+    {
+        if self.scl && scl && sda != self.sda {
+            if sda { self.stop() } else { self.start() }
+        }
+        else if !self.scl && scl {
+            self.clock_in(sda);
+        }
+        else if self.scl && !scl {
+            self.clock_out();
+        }
+
+        self.scl = scl;
+        self.sda = sda;
+    }
+
+    // SDA as driven by the EEPROM, false while it acknowledges or outputs a 0
+    pub fn get_sda(&self) -> bool
+    {
+        self.output
+    }
+
+    pub fn get_data(&self) -> &[u8]
+    {
+        &self.data
+    }
+
+    pub fn load(&mut self, data: &[u8]) -> Result<(), Box<dyn Error>>
+    {
+        if data.len() != SIZE {
+            return Err(format!("EEPROM image must be {} bytes, got {}", SIZE, data.len()).into());
+        }
+
+        self.data.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn start(&mut self)
+    {
+        self.phase = Phase::Device;
+        self.shift = 0;
+        self.bit = 0;
+        self.output = true;
+    }
+
+    fn stop(&mut self)
+    {
+        self.phase = Phase::Idle;
+        self.output = true;
+    }
+
+    fn clock_in(&mut self, sda: bool)
+    {
+        match self.phase {
+            Phase::Idle => return,
+            Phase::Read => {
+                // The master pulls SDA low after a byte to ask for the next one
+                if self.bit == 8 {
+                    self.read_ack = !sda;
+                }
+            },
+            _ => {
+                if self.bit < 8 {
+                    self.shift = (self.shift << 1) | sda as u8;
+                }
+            }
+        }
+
+        self.bit += 1;
+    }
+
+    fn clock_out(&mut self)
+    {
+        match (self.phase, self.bit) {
+            (Phase::Idle, _) => {},
+            (Phase::Read, 1..=7) => self.output = self.shift & (0x80 >> self.bit) != 0,
+            (Phase::Read, 8) => self.output = true,
+            (Phase::Read, 9) => {
+                if self.read_ack {
+                    self.addr = self.addr.wrapping_add(1);
+                    self.begin_read();
+                }
+                else {
+                    self.stop();
+                }
+            },
+            // Drive the acknowledge bit for the byte just received
+            (_, 8) => {
+                let ack = self.receive();
+                self.output = !ack;
+            },
+            (_, 9) => {
+                self.phase = self.next;
+                self.shift = 0;
+                self.bit = 0;
+                self.output = true;
+                if self.phase == Phase::Read {
+                    self.begin_read();
+                }
+            },
+            _ => {}
+        }
+    }
+
+    // Handles a complete byte from the master, returns whether to acknowledge it
+    fn receive(&mut self) -> bool
+    {
+        let byte = self.shift;
+        match self.phase {
+            Phase::Device if byte & !READ != DEVICE_ADDRESS => {
+                self.next = Phase::Idle;
+                return false;
+            },
+            Phase::Device => {
+                self.next = if byte & READ != 0 { Phase::Read } else { Phase::Address };
+            },
+            Phase::Address => {
+                self.addr = byte;
+                self.next = Phase::Write;
+            },
+            Phase::Write => {
+                self.data[self.addr as usize] = byte;
+                self.addr = (self.addr & !PAGE_MASK) | (self.addr.wrapping_add(1) & PAGE_MASK);
+                self.next = Phase::Write;
+            },
+            Phase::Idle | Phase::Read => return false
+        }
+        true
+    }
+
+    // Puts the MSB of the byte at the current address on SDA
+    fn begin_read(&mut self)
+    {
+        self.shift = self.data[self.addr as usize];
+        self.bit = 0;
+        self.output = self.shift & 0x80 != 0;
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::Eeprom24C02;
+
+    fn start(eeprom: &mut Eeprom24C02)
+    {
+        eeprom.set_lines(false, true);
+        eeprom.set_lines(true, true);
+        eeprom.set_lines(true, false);
+        eeprom.set_lines(false, false);
+    }
+
+    fn stop(eeprom: &mut Eeprom24C02)
+    {
+        eeprom.set_lines(false, false);
+        eeprom.set_lines(true, false);
+        eeprom.set_lines(true, true);
+    }
+
+    fn clock(eeprom: &mut Eeprom24C02, sda: bool) -> bool
+    {
+        eeprom.set_lines(false, sda);
+        eeprom.set_lines(true, sda);
+        let out = eeprom.get_sda();
+        eeprom.set_lines(false, sda);
+        out
+    }
+
+    // Sends a byte and returns whether the EEPROM acknowledged it
+    fn send(eeprom: &mut Eeprom24C02, byte: u8) -> bool
+    {
+        for i in (0..8).rev() {
+            clock(eeprom, byte & (1 << i) != 0);
+        }
+        !clock(eeprom, true)
+    }
+
+    fn receive(eeprom: &mut Eeprom24C02, ack: bool) -> u8
+    {
+        let byte = (0..8).fold(0, |acc, _| (acc << 1) | clock(eeprom, true) as u8);
+        clock(eeprom, !ack);
+        byte
+    }
+
+    fn write(eeprom: &mut Eeprom24C02, addr: u8, data: &[u8])
+    {
+        start(eeprom);
+        assert!(send(eeprom, 0xA0));
+        assert!(send(eeprom, addr));
+        for byte in data {
+            assert!(send(eeprom, *byte));
+        }
+        stop(eeprom);
+    }
+
+    #[test]
+    fn write_then_random_read()
+    {
+        let mut eeprom = Eeprom24C02::new();
+        write(&mut eeprom, 0x10, &[0x12, 0x34, 0x56]);
+
+        start(&mut eeprom);
+        assert!(send(&mut eeprom, 0xA0));
+        assert!(send(&mut eeprom, 0x11));
+        start(&mut eeprom);
+        assert!(send(&mut eeprom, 0xA1));
+        assert_eq!(0x34, receive(&mut eeprom, true));
+        assert_eq!(0x56, receive(&mut eeprom, false));
+        stop(&mut eeprom);
+
+        assert_eq!(&[0x12, 0x34, 0x56], &eeprom.get_data()[0x10..0x13]);
+    }
+
+    #[test]
+    fn sequential_read_wraps()
+    {
+        let mut eeprom = Eeprom24C02::new();
+        let data: Vec<u8> = (0..=0xFF).collect();
+        eeprom.load(&data).unwrap();
+        write(&mut eeprom, 0xFE, &[]);
+
+        start(&mut eeprom);
+        assert!(send(&mut eeprom, 0xA1));
+        assert_eq!(0xFE, receive(&mut eeprom, true));
+        assert_eq!(0xFF, receive(&mut eeprom, true));
+        assert_eq!(0x00, receive(&mut eeprom, false));
+        stop(&mut eeprom);
+    }
+
+    #[test]
+    fn page_write_wraps()
+    {
+        let mut eeprom = Eeprom24C02::new();
+        write(&mut eeprom, 0x06, &[1, 2, 3]);
+
+        assert_eq!(3, eeprom.get_data()[0x00]);
+        assert_eq!(&[1, 2], &eeprom.get_data()[0x06..0x08]);
+        assert_eq!(0xFF, eeprom.get_data()[0x08]);
+    }
+
+    #[test]
+    fn other_device_ignored()
+    {
+        let mut eeprom = Eeprom24C02::new();
+        start(&mut eeprom);
+
+        assert!(!send(&mut eeprom, 0xA2));
+        assert!(!send(&mut eeprom, 0x00));
+        assert!(eeprom.get_sda());
+    }
+
+    #[test]
+    fn save_and_load()
+    {
+        let mut eeprom = Eeprom24C02::new();
+        write(&mut eeprom, 0x42, &[0x99]);
+
+        let mut restored = Eeprom24C02::new();
+        restored.load(eeprom.get_data()).unwrap();
+        assert_eq!(0x99, restored.get_data()[0x42]);
+        assert!(restored.load(&[0; 0x80]).is_err());
+    }
+}
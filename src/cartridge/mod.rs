@@ -0,0 +1,5 @@
+pub mod eeprom;
+mod ram;
+
+pub use self::eeprom::Eeprom24C02;
+pub use self::ram::CartridgeRam;
@@ -0,0 +1,91 @@
+use std::error::Error;
+
+// PRG or CHR RAM on the cartridge. The battery-backed part comes first and is
+// what gets saved, the rest is cleared on power-up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CartridgeRam
+{
+    data: Vec<u8>,
+    nvram_size: usize
+}
+
+impl CartridgeRam
+{
+    pub fn new(ram_size: usize, nvram_size: usize) -> CartridgeRam
+    {
+        CartridgeRam { data: vec![0; nvram_size + ram_size], nvram_size }
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.data.is_empty()
+    }
+
+    pub fn is_persistent(&self) -> bool
+    {
+        self.nvram_size > 0
+    }
+
+    pub fn get_data(&self) -> &[u8]
+    {
+        &self.data
+    }
+
+    pub fn get_data_mut(&mut self) -> &mut [u8]
+    {
+        &mut self.data
+    }
+
+    // Contents of the battery-backed part, for writing a save file
+    pub fn save(&self) -> &[u8]
+    {
+        &self.data[..self.nvram_size]
+    }
+
+    pub fn load(&mut self, save: &[u8]) -> Result<(), Box<dyn Error>>
+    {
+        if save.len() != self.nvram_size {
+            return Err(format!("Save data must be {} bytes, got {}", self.nvram_size, save.len()).into());
+        }
+
+        self.data[..self.nvram_size].copy_from_slice(save);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::CartridgeRam;
+
+    #[test]
+    fn save_and_load()
+    {
+        let mut ram = CartridgeRam::new(0x2000, 0x800);
+        ram.get_data_mut()[0x7FF] = 0x42;
+        ram.get_data_mut()[0x800] = 0x24;
+
+        let save = ram.save().to_vec();
+        assert_eq!(0x800, save.len());
+
+        let mut restored = CartridgeRam::new(0x2000, 0x800);
+        restored.load(&save).unwrap();
+        assert_eq!(0x42, restored.get_data()[0x7FF]);
+        assert_eq!(0, restored.get_data()[0x800]);
+        assert_eq!(0x2800, restored.len());
+    }
+
+    #[test]
+    fn load_wrong_size()
+    {
+        let mut ram = CartridgeRam::new(0, 0x800);
+
+        assert!(ram.load(&[0; 0x400]).is_err());
+        assert!(!CartridgeRam::new(0x2000, 0).is_persistent());
+    }
+}
@@ -1,5 +1,42 @@
-use crate::bus::Bus;
+use std::collections::VecDeque;
+
+use crate::bus::{Access, BusInterface};
+#[cfg(feature = "debugger")]
+use crate::debugger::expr::{ExprContext, Register};
 use self::addressing::{AddressMode, Value};
+use self::journal::Journal;
+
+pub use self::crash::{CrashReport, TraceLine};
+pub use self::error::EmulationFault;
+
+mod error
+{
+    use std::{fmt::Display, error::Error};
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum EmulationFault
+    {
+        UnknownOpcode { pc: u16, op_code: u8 },
+        BusFault(u16),
+        Jammed { pc: u16, op_code: u8 },
+        InfiniteLoop(u16)
+    }
+
+    impl Display for EmulationFault
+    {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+        {
+            match self {
+                EmulationFault::UnknownOpcode { pc, op_code } => write!(f, "Unknown opcode {:#04X} at {:#06X}", op_code, pc),
+                EmulationFault::BusFault(addr) => write!(f, "Access to unmapped address {:#06X}", addr),
+                EmulationFault::Jammed { pc, op_code } => write!(f, "CPU jammed by opcode {:#04X} at {:#06X}", op_code, pc),
+                EmulationFault::InfiniteLoop(pc) => write!(f, "CPU stuck in an infinite loop at {:#06X}", pc)
+            }
+        }
+    }
+
+    impl Error for EmulationFault {}
+}
 
 mod addressing
 {
@@ -12,9 +49,10 @@ mod addressing
         AtAddress(u16)
     }
 
+    #[allow(dead_code)]
     impl Value
     {
-        pub fn get(&self, cpu: &CPU) -> u8 
+        pub fn get(&self, cpu: &mut CPU) -> u8 
         {
             match self {
                 Value::FromAccumulator => cpu.registers.A,
@@ -40,13 +78,15 @@ mod addressing
         pub pc_offset: u16
     }    
 
+    #[allow(dead_code)]
     pub enum AddressMode
     {
         None,
         Acc,
         Imm,
         Zp,
-        Zpx
+        Zpx,
+        Abs
     }
     
     impl AddressMode
@@ -83,11 +123,19 @@ mod addressing
                     }
                 },
                 AddressMode::Zpx => {
+                    // The index wraps around within the zero page
                     AccessResult {
-                        value: Value::AtAddress(cpu.bus.read8(cpu.registers.PC) as u16 + cpu.registers.X as u16),
+                        value: Value::AtAddress(cpu.bus.read8(cpu.registers.PC).wrapping_add(cpu.registers.X) as u16),
                         cycles: 4,
                         pc_offset: 1
                     }
+                },
+                AddressMode::Abs => {
+                    AccessResult {
+                        value: Value::AtAddress(cpu.bus.read16(cpu.registers.PC)),
+                        cycles: 4,
+                        pc_offset: 2
+                    }
                 }
             }    
         }
@@ -95,6 +143,133 @@ mod addressing
     
 }
 
+mod crash
+{
+    use std::fmt::Display;
+    use super::{EmulationFault, RegisterState};
+
+    // CPU state at the start of an instruction
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct TraceLine
+    {
+        pub registers: RegisterState,
+        pub op_code: u8,
+        pub cycle: usize
+    }
+
+    impl Display for TraceLine
+    {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+        {
+            let regs = &self.registers;
+            write!(f, "{:04X}  {:02X}  A:{:02X} X:{:02X} Y:{:02X} P:{} SP:{:02X} CYC:{}",
+                regs.pc, self.op_code, regs.a, regs.x, regs.y, regs.ps, regs.sp, self.cycle)
+        }
+    }
+
+    // Snapshot taken when a fault stops emulation, for bug reports
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct CrashReport
+    {
+        pub fault: EmulationFault,
+        pub registers: RegisterState,
+        pub cycle: usize,
+        pub trace: Vec<TraceLine>,
+        pub stack: Vec<u8>
+    }
+
+    impl Display for CrashReport
+    {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+        {
+            let regs = &self.registers;
+            writeln!(f, "{} at cycle {}", self.fault, self.cycle)?;
+            writeln!(f, "PC:{:04X} A:{:02X} X:{:02X} Y:{:02X} P:{} SP:{:02X}",
+                regs.pc, regs.a, regs.x, regs.y, regs.ps, regs.sp)?;
+
+            if !self.trace.is_empty() {
+                writeln!(f, "\nLast instructions:")?;
+                for line in &self.trace {
+                    writeln!(f, "{}", line)?;
+                }
+            }
+
+            writeln!(f, "\nStack page:")?;
+            for (i, row) in self.stack.chunks(16).enumerate() {
+                write!(f, "{:04X}:", 0x100 + i * 16)?;
+                for byte in row {
+                    write!(f, " {:02X}", byte)?;
+                }
+                writeln!(f)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+mod journal
+{
+    use std::collections::VecDeque;
+    use super::Registers;
+
+    // State needed to undo one instruction
+    pub struct JournalEntry
+    {
+        pub registers: Registers,
+        pub cycle: usize,
+        pub halted: bool,
+        pub writes: Vec<(u16, u8)>
+    }
+
+    pub struct Journal
+    {
+        entries: VecDeque<JournalEntry>,
+        capacity: usize
+    }
+
+    impl Journal
+    {
+        pub fn new(capacity: usize) -> Journal
+        {
+            Journal {
+                entries: VecDeque::with_capacity(capacity),
+                capacity
+            }
+        }
+
+        // Starts a new entry. Once the journal is full the oldest entry is
+        // dropped and its write buffer reused for the new one.
+        pub fn push(&mut self, registers: Registers, cycle: usize, halted: bool)
+        {
+            let mut writes = Vec::new();
+            if self.entries.len() == self.capacity {
+                if let Some(oldest) = self.entries.pop_front() {
+                    writes = oldest.writes;
+                    writes.clear();
+                }
+            }
+
+            self.entries.push_back(JournalEntry { registers, cycle, halted, writes });
+        }
+
+        pub fn last_mut(&mut self) -> Option<&mut JournalEntry>
+        {
+            self.entries.back_mut()
+        }
+
+        pub fn pop(&mut self) -> Option<JournalEntry>
+        {
+            self.entries.pop_back()
+        }
+    }
+}
+
+mod vector
+{
+    pub const NMI: u16 = 0xFFFA;
+    pub const IRQ: u16 = 0xFFFE;
+}
+
 type OpImpl = fn(&mut CPU, operand: &mut Value);
 
 pub struct Op
@@ -110,10 +285,10 @@ impl Op
     fn new(cpu: &mut CPU, op_impl: OpImpl, addr_mode: AddressMode) -> Op
     {
         let result = addr_mode.read(cpu);
-        cpu.registers.PC += result.pc_offset;
+        cpu.registers.PC = cpu.registers.PC.wrapping_add(result.pc_offset);
 
         Op {
-            op_impl: op_impl,
+            op_impl,
             operand: result.value,
             total_cycles: result.cycles,
             cycle: 0
@@ -132,7 +307,8 @@ impl Op
     }
 }
 
-enum StatusFlags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusFlags
 {
     C = 0b00000001,
     Z = 0b00000010,
@@ -144,6 +320,67 @@ enum StatusFlags
     N = 0b10000000
 }
 
+// Processor status register. Displays as NV-BDIZC with set flags in upper
+// case and cleared ones in lower case.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct Flags(u8);
+
+impl Flags
+{
+    pub fn from_bits(bits: u8) -> Flags
+    {
+        Flags(bits)
+    }
+
+    pub fn bits(&self) -> u8
+    {
+        self.0
+    }
+
+    #[inline(always)]
+    pub fn get(&self, flag: StatusFlags) -> bool
+    {
+        self.0 & flag as u8 > 0
+    }
+
+    #[inline(always)]
+    pub fn set(&mut self, flag: StatusFlags, is_set: bool)
+    {
+        if is_set {
+            self.0 |= flag as u8;
+        }
+        else {
+            self.0 &= !(flag as u8);
+        }
+    }
+}
+
+impl std::fmt::Display for Flags
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        let flags = [
+            (StatusFlags::N, 'N'), (StatusFlags::V, 'V'), (StatusFlags::_1, '-'), (StatusFlags::B, 'B'),
+            (StatusFlags::D, 'D'), (StatusFlags::I, 'I'), (StatusFlags::Z, 'Z'), (StatusFlags::C, 'C')
+        ];
+        for (flag, name) in flags {
+            let name = if self.get(flag) { name } else { name.to_ascii_lowercase() };
+            write!(f, "{}", name)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Flags
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f, "Flags({})", self)
+    }
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Copy)]
 struct Registers
 {
     PC: u16,
@@ -151,7 +388,7 @@ struct Registers
     A : u8,
     X : u8,
     Y : u8,
-    PS: u8,
+    PS: Flags,
 }
 
 impl Registers
@@ -159,32 +396,67 @@ impl Registers
     #[inline(always)]
     fn get_flag(&self, flag: StatusFlags) -> bool
     {
-        self.PS & flag as u8 > 0
+        self.PS.get(flag)
     }
 
     #[inline(always)]
     fn set_flag(&mut self, flag: StatusFlags, is_set:bool)
     {
-        if is_set {
-            self.PS = self.PS | flag as u8;
-        }
-        else {
-            self.PS = self.PS & !(flag as u8);
-        }
+        self.PS.set(flag, is_set);
     }
 }
 
+// Read-only copy of the CPU registers for tools and frontends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterState
+{
+    pub pc: u16,
+    pub sp: u8,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub ps: Flags
+}
+
+// Outcome of CPU::run_cycles, `ran` includes the cycle that faulted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CycleRun
+{
+    pub ran: u64,
+    pub fault: Option<EmulationFault>
+}
+
+// Strict mode reports faults to the caller, lenient mode carries on: unknown
+// opcodes execute as NOP and unmapped reads return open bus. Infinite loops
+// are reported in both modes, as detecting them is opt-in.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FaultMode
+{
+    Strict,
+    Lenient
+}
+
 pub struct CPU
 {
-    bus: Box<Bus>,
+    bus: Box<dyn BusInterface>,
     registers: Registers,
     cycle: usize,
-    op: Option<Op>
+    op: Option<Op>,
+    op_pc: u16,
+    op_code: u8,
+    halted: bool,
+    loop_detection: bool,
+    journal: Option<Journal>,
+    trace: VecDeque<TraceLine>,
+    trace_capacity: usize,
+    crash_report: Option<CrashReport>,
+    fault: Option<EmulationFault>,
+    fault_mode: FaultMode
 }
 
 impl CPU
 {
-    pub fn new(bus: Box<Bus>) -> CPU
+    pub fn new(bus: Box<dyn BusInterface>) -> CPU
     {
         CPU {
             bus,
@@ -194,176 +466,1087 @@ impl CPU
                 A : 0,
                 X : 0,
                 Y : 0,
-                PS: 0,
+                PS: Flags::default(),
             },
             cycle: 0,
-            op: None
+            op: None,
+            op_pc: 0,
+            op_code: 0,
+            halted: false,
+            loop_detection: false,
+            journal: None,
+            trace: VecDeque::new(),
+            trace_capacity: 0,
+            crash_report: None,
+            fault: None,
+            fault_mode: FaultMode::Strict
         }
     } 
 
-    pub fn tick(&mut self)
+    pub fn set_fault_mode(&mut self, mode: FaultMode)
     {
-        if self.op.is_none() {
-            self.op = Some(self.read_op());
-        }
+        self.fault_mode = mode;
+    }
 
-        let cur_op = self.op.take();
-        let mut op = cur_op.unwrap();
-        if op.tick(self) {
-            self.op = None
-        }
-        else {
-            self.op = Some(op);
-        }
+    pub fn get_fault_mode(&self) -> FaultMode
+    {
+        self.fault_mode
+    }
 
-        self.cycle += 1;
+    // Accesses to these addresses or their mirrors are recorded for read and
+    // write breakpoints, see Breakpoints::get_watchpoints
+    pub fn set_watchpoints(&mut self, addrs: &[u16])
+    {
+        self.bus.set_watchpoints(addrs);
     }
 
-    pub fn ticks(&mut self, n:usize) 
+    pub fn take_watch_hits(&mut self) -> Vec<Access>
     {
-        for _ in 0..n {
-            self.tick();
-        }
+        self.bus.take_watch_hits()
     }
 
-    fn read_op(&mut self) -> Op
+    // Reports instructions that branch back to their own address as InfiniteLoop,
+    // unless an interrupt can still get the CPU out of it. JMP to itself is
+    // also how programs wait for NMI.
+    pub fn set_loop_detection(&mut self, enabled: bool)
     {
-        let op_code = self.bus.read8(self.registers.PC);
-        self.registers.PC += 1;
-        let op_factory = instructions::OPCODE_MAP[op_code as usize];
-        op_factory(self)
+        self.loop_detection = enabled;
     }
-}
 
-mod instructions
-{
-    use super::{CPU, StatusFlags, addressing::{AddressMode, Value}, Op};
+    // Set once a KIL opcode has been executed, the CPU stops fetching instructions
+    pub fn is_halted(&self) -> bool
+    {
+        self.halted
+    }
 
-    pub const OPCODE_MAP: [fn(&mut CPU) -> Op; 0x100] = [
-      //       0       1       2       3       4       5       6       7       8       9       A       B       C       D       E       F
-      /* 0 */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,
-      /* 1 */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,
-      /* 2 */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,
-      /* 3 */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,
-      /* 4 */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,
-      /* 5 */ nop,    nop,    nop,    nop,    nop,    nop,  adc_zp, adc_zpx,  nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,
-      /* 6 */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,  adc_imm,  nop,    nop,    nop,    nop,    nop,    nop,
-      /* 7 */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,
-      /* 8 */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,
-      /* 9 */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,
-      /* A */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,
-      /* B */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,
-      /* C */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,
-      /* D */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,
-      /* E */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,
-      /* F */ nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,    nop,        
-    ];
+    pub fn tick(&mut self) -> Result<(), EmulationFault>
+    {
+        if !self.halted {
+            self.tick_op();
+        }
 
-    fn nop(cpu: &mut CPU) -> Op
+        self.bus.tick();
+        self.cycle += 1;
+
+        // A bus fault is the root cause of anything the instruction ran into afterwards
+        let bus_fault = self.bus.take_fault().map(EmulationFault::BusFault);
+        let fault = bus_fault.or(self.fault.take());
+        match fault {
+            Some(fault) if self.fault_mode == FaultMode::Strict || matches!(fault, EmulationFault::InfiniteLoop(_)) => {
+                self.crash_report = Some(self.crash_report(fault));
+                Err(fault)
+            },
+            _ => Ok(())
+        }
+    }
+
+    // Keeps the CPU state of the last `capacity` instructions for crash
+    // reports. A capacity of 0 disables tracing.
+    pub fn set_trace_capacity(&mut self, capacity: usize)
     {
-        Op::new(cpu, |_, _|{}, AddressMode::None)
+        self.trace_capacity = capacity;
+        self.trace = VecDeque::with_capacity(capacity);
     }
 
-    fn adc_imm(cpu: &mut CPU) -> Op
-    {      
-        Op::new(cpu, _adc, AddressMode::Imm)
+    // Returns the report of the last fault that stopped emulation
+    pub fn take_crash_report(&mut self) -> Option<CrashReport>
+    {
+        self.crash_report.take()
     }
 
-    fn adc_zp(cpu: &mut CPU) -> Op
-    {        
-        Op::new(cpu, _adc, AddressMode::Zp)
+    fn crash_report(&self, fault: EmulationFault) -> CrashReport
+    {
+        CrashReport {
+            fault,
+            registers: self.get_registers(),
+            cycle: self.cycle,
+            trace: self.trace.iter().copied().collect(),
+            stack: (0x100..0x200).map(|addr| self.bus.peek8(addr)).collect()
+        }
     }
 
-    fn adc_zpx(cpu: &mut CPU) -> Op
-    {        
-        Op::new(cpu, _adc, AddressMode::Zpx)
+    pub fn ticks(&mut self, n:usize) -> Result<(), EmulationFault>
+    {
+        for _ in 0..n {
+            self.tick()?;
+        }
+
+        Ok(())
     }
 
-    fn _adc(cpu: &mut CPU, arg: &mut Value)
+    // Runs at most n cycles and returns how many ran, which is n unless a fault
+    // stops it early. The CPU is cycle-stepped, so an instruction cut off at
+    // the end of the budget simply resumes on the next call. This lets
+    // frontends slice emulation by audio buffer size rather than by frame.
+    pub fn run_cycles(&mut self, n: u64) -> CycleRun
     {
-        let base = cpu.registers.A as u16;
-        let operand = arg.get(cpu) as u16;
-        let mut result: u16 = base + operand;
-        if cpu.registers.get_flag(StatusFlags::C) {
-            result += 1;
-        }        
+        let mut ran = 0;
+        while ran < n {
+            let result = self.tick();
+            ran += 1;
 
-        cpu.registers.set_flag(StatusFlags::C, result > 0xFF);
-        
-        let operand = operand as u8;
-        let result: u8 = result as u8;
-        cpu.registers.set_flag(StatusFlags::Z, result == 0);
-        cpu.registers.set_flag(StatusFlags::V, (operand ^ result) & (result ^ cpu.registers.A) & 0x80 != 0);
-        cpu.registers.set_flag(StatusFlags::N, result & 0b10000000 > 0);
+            if let Err(fault) = result {
+                return CycleRun { ran, fault: Some(fault) };
+            }
+        }
 
-        cpu.registers.A = result as u8;
+        CycleRun { ran, fault: None }
     }
-}
-
-#[cfg(test)]
-mod tests
-{
-    use crate::bus::Bus;
-    use super::CPU;
 
-    fn load_program(program: Vec<u8>) -> CPU
+    pub fn get_cycle(&self) -> usize
     {
-        let mut bus = Bus::new();
-        bus.write_buffer(0, &program);
-        CPU::new(Box::new(bus))
+        self.cycle
     }
 
-    mod adc
+    // Reads memory without side effects on the devices
+    pub fn peek8(&self, addr: u16) -> u8
     {
-        use std::vec;
-        use crate::cpu::{tests::load_program, StatusFlags};
+        self.bus.peek8(addr)
+    }
 
-        #[test]
-        fn adc_imm()
-        {
-            let mut cpu = load_program(vec![0x69, 0x02]);
-            cpu.ticks(2);
-            assert_eq!(cpu.registers.A, 2);
+    pub fn get_registers(&self) -> RegisterState
+    {
+        RegisterState {
+            pc: self.registers.PC,
+            sp: self.registers.SP,
+            a: self.registers.A,
+            x: self.registers.X,
+            y: self.registers.Y,
+            ps: self.registers.PS
         }
+    }
 
-        #[test]
-        fn adc_imm_multiple()
-        {
-            let mut cpu = load_program(vec![
-                0x69, 0x02,
-                0x69, 0x03,
-                0x69, 0x04
-            ]);
+    // Meant for setting up a test or a debugger edit between instructions,
+    // an instruction in flight keeps running with the new values
+    pub fn set_registers(&mut self, state: RegisterState)
+    {
+        self.registers.PC = state.pc;
+        self.registers.SP = state.sp;
+        self.registers.A = state.a;
+        self.registers.X = state.x;
+        self.registers.Y = state.y;
+        self.registers.PS = state.ps;
+    }
+
+    // Keeps the last `capacity` instructions so they can be undone with
+    // step_back(). Only CPU registers and RAM are restored, other devices keep
+    // their state. A capacity of 0 disables the journal.
+    pub fn set_journal_capacity(&mut self, capacity: usize)
+    {
+        self.journal = if capacity > 0 { Some(Journal::new(capacity)) } else { None };
+        self.bus.set_write_log(capacity > 0);
+    }
 
-            cpu.ticks(6);
+    // Undoes the last completed instruction, or rewinds the one in progress to
+    // its start. Returns false when the journal is empty.
+    pub fn step_back(&mut self) -> bool
+    {
+        self.update_journal();
+        let Some(mut entry) = self.journal.as_mut().and_then(|journal| journal.pop()) else {
+            return false;
+        };
 
-            assert_eq!(cpu.registers.A, 9);
+        for &(addr, val) in entry.writes.iter().rev() {
+            self.bus.write8(addr, val);
         }
+        // Drop the restoring writes from the log
+        entry.writes.clear();
+        self.bus.drain_write_log(&mut entry.writes);
 
-        #[test]
-        fn adc_imm_z_flag_set()
-        {
-            let mut cpu = load_program(vec![
-                0x69, 0xFF,
-                0x69, 0x01
-            ]);
-
-            cpu.ticks(4);
+        self.registers = entry.registers;
+        self.cycle = entry.cycle;
+        self.halted = entry.halted;
+        self.op = None;
+        self.fault = None;
+        true
+    }
 
-            assert!(cpu.registers.get_flag(StatusFlags::Z));
+    // Runs until the current instruction has completed
+    pub fn step(&mut self) -> Result<(), EmulationFault>
+    {
+        self.tick()?;
+        while self.op.is_some() {
+            self.tick()?;
         }
 
-        #[test]
-        fn adc_imm_z_flag_unset()
-        {
-            let mut cpu = load_program(vec![
-                0x69, 0x01,
-                0x69, 0x02
-            ]);
+        Ok(())
+    }
 
-            cpu.ticks(4);
+    fn tick_op(&mut self)
+    {
+        if self.op.is_none() {
+            self.op = Some(self.read_op());
+        }
 
-            assert!(!cpu.registers.get_flag(StatusFlags::Z));
+        let cur_op = self.op.take();
+        let mut op = cur_op.unwrap();
+        if !op.tick(self) {
+            self.op = Some(op);
+            return;
+        }
+
+        if self.loop_detection && self.registers.PC == self.op_pc && !self.is_interrupt_possible() {
+            self.raise_fault(EmulationFault::InfiniteLoop(self.op_pc));
+        }
+    }
+
+    fn is_interrupt_possible(&self) -> bool
+    {
+        self.bus.is_nmi_enabled() || (self.bus.is_irq_asserted() && !self.registers.get_flag(StatusFlags::I))
+    }
+
+    // Moves RAM writes made since the last call into the current journal entry
+    fn update_journal(&mut self)
+    {
+        if let Some(entry) = self.journal.as_mut().and_then(|journal| journal.last_mut()) {
+            self.bus.drain_write_log(&mut entry.writes);
+        }
+    }
+
+    fn push8(&mut self, val: u8)
+    {
+        self.bus.write8(0x100 | self.registers.SP as u16, val);
+        self.registers.SP = self.registers.SP.wrapping_sub(1);
+    }
+
+    fn push16(&mut self, val: u16)
+    {
+        self.push8((val >> 8) as u8);
+        self.push8(val as u8);
+    }
+
+    fn pull8(&mut self) -> u8
+    {
+        self.registers.SP = self.registers.SP.wrapping_add(1);
+        self.bus.read8(0x100 | self.registers.SP as u16)
+    }
+
+    fn pull16(&mut self) -> u16
+    {
+        let lo = self.pull8() as u16;
+        let hi = self.pull8() as u16;
+        (hi << 8) | lo
+    }
+
+    fn raise_fault(&mut self, fault: EmulationFault)
+    {
+        if self.fault.is_none() {
+            self.fault = Some(fault);
+        }
+    }
+
+    fn read_op(&mut self) -> Op
+    {
+        if self.journal.is_some() {
+            self.update_journal();
+            let (registers, cycle, halted) = (self.registers, self.cycle, self.halted);
+            self.journal.as_mut().unwrap().push(registers, cycle, halted);
+        }
+
+        self.op_pc = self.registers.PC;
+
+        // Interrupts are polled between instructions, NMI takes priority
+        let interrupt = if self.bus.take_nmi() {
+            Some(vector::NMI)
+        }
+        else if self.bus.is_irq_asserted() && !self.registers.get_flag(StatusFlags::I) {
+            Some(vector::IRQ)
+        }
+        else {
+            None
+        };
+
+        if let Some(vector) = interrupt {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(pc = self.registers.PC, vector, cycle = self.cycle, "interrupt");
+
+            return instructions::interrupt(vector);
+        }
+
+        let op_code = self.bus.read8(self.registers.PC);
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(pc = self.registers.PC, op_code, cycle = self.cycle, "fetch instruction");
+
+        if self.trace_capacity > 0 {
+            if self.trace.len() == self.trace_capacity {
+                self.trace.pop_front();
+            }
+            self.trace.push_back(TraceLine { registers: self.get_registers(), op_code, cycle: self.cycle });
+        }
+
+        self.op_code = op_code;
+        self.registers.PC = self.registers.PC.wrapping_add(1);
+        let op_factory = instructions::OPCODE_MAP[op_code as usize];
+        op_factory(self)
+    }
+}
+
+#[cfg(feature = "debugger")]
+impl ExprContext for CPU
+{
+    fn get_register(&self, reg: Register) -> u16
+    {
+        match reg {
+            Register::A => self.registers.A as u16,
+            Register::X => self.registers.X as u16,
+            Register::Y => self.registers.Y as u16,
+            Register::SP => self.registers.SP as u16,
+            Register::PC => self.registers.PC,
+            Register::PS => self.registers.PS.bits() as u16
+        }
+    }
+
+    fn peek8(&self, addr: u16) -> u8
+    {
+        self.bus.peek8(addr)
+    }
+}
+
+mod instructions
+{
+    use super::{CPU, StatusFlags, EmulationFault, addressing::{AddressMode, Value}, Op};
+
+    pub const OPCODE_MAP: [fn(&mut CPU) -> Op; 0x100] = [
+      //       0       1       2       3       4       5       6       7       8       9       A       B       C       D       E       F
+      /* 0 */ unk,    unk,    kil,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,
+      /* 1 */ unk,    unk,    kil,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,
+      /* 2 */ jsr_abs, unk,   kil,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,
+      /* 3 */ unk,    unk,    kil,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,
+      /* 4 */ unk,    unk,    kil,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,  jmp_abs,  unk,    unk,    unk,
+      /* 5 */ unk,    unk,    kil,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,
+      /* 6 */ rts,    unk,    kil,    unk,    unk,  adc_zp,   unk,    unk,    unk,  adc_imm,  unk,    unk,    unk,    unk,    unk,    unk,
+      /* 7 */ unk,    unk,    kil,    unk,    unk, adc_zpx,   unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,
+      /* 8 */ unk,    unk,    unk,    unk,    unk,  sta_zp,   unk,    unk,    unk,    unk,    unk,    unk,    unk,  sta_abs,  unk,    unk,
+      /* 9 */ unk,    unk,    kil,    unk,    unk, sta_zpx,   unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,
+      /* A */ unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,  lda_abs,  unk,    unk,
+      /* B */ unk,    unk,    kil,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,
+      /* C */ unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,
+      /* D */ unk,    unk,    kil,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,
+      /* E */ unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    nop,    unk,    unk,    unk,    unk,    unk,
+      /* F */ unk,    unk,    kil,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,    unk,        
+    ];
+
+    // Hardware interrupt sequence, runs in place of an instruction
+    pub fn interrupt(vector: u16) -> Op
+    {
+        Op {
+            op_impl: _interrupt,
+            operand: Value::AtAddress(vector),
+            cycle: 0,
+            total_cycles: 7
+        }
+    }
+
+    fn _interrupt(cpu: &mut CPU, arg: &mut Value)
+    {
+        if let Value::AtAddress(vector) = arg {
+            cpu.push16(cpu.registers.PC);
+            // B is only set in the copy pushed by BRK and PHP
+            cpu.push8((cpu.registers.PS.bits() | StatusFlags::_1 as u8) & !(StatusFlags::B as u8));
+            cpu.registers.set_flag(StatusFlags::I, true);
+            cpu.registers.PC = cpu.bus.read16(*vector);
+        }
+    }
+
+    fn nop(cpu: &mut CPU) -> Op
+    {
+        Op::new(cpu, |_, _|{}, AddressMode::None)
+    }
+
+    fn unk(cpu: &mut CPU) -> Op
+    {
+        cpu.raise_fault(EmulationFault::UnknownOpcode { pc: cpu.op_pc, op_code: cpu.op_code });
+        nop(cpu)
+    }
+
+    fn kil(cpu: &mut CPU) -> Op
+    {
+        cpu.halted = true;
+        cpu.raise_fault(EmulationFault::Jammed { pc: cpu.op_pc, op_code: cpu.op_code });
+        nop(cpu)
+    }
+
+    fn jmp_abs(cpu: &mut CPU) -> Op
+    {
+        let mut op = Op::new(cpu, _jmp, AddressMode::Abs);
+        op.total_cycles = 3;
+        op
+    }
+
+    fn _jmp(cpu: &mut CPU, arg: &mut Value)
+    {
+        if let Value::AtAddress(addr) = arg {
+            cpu.registers.PC = *addr;
+        }
+    }
+
+    fn jsr_abs(cpu: &mut CPU) -> Op
+    {
+        let mut op = Op::new(cpu, _jsr, AddressMode::Abs);
+        op.total_cycles = 6;
+        op
+    }
+
+    fn _jsr(cpu: &mut CPU, arg: &mut Value)
+    {
+        if let Value::AtAddress(addr) = arg {
+            // The return address pushed is the last byte of the JSR
+            cpu.push16(cpu.registers.PC.wrapping_sub(1));
+            cpu.registers.PC = *addr;
+        }
+    }
+
+    fn rts(cpu: &mut CPU) -> Op
+    {
+        let mut op = Op::new(cpu, _rts, AddressMode::None);
+        op.total_cycles = 6;
+        op
+    }
+
+    fn _rts(cpu: &mut CPU, _arg: &mut Value)
+    {
+        cpu.registers.PC = cpu.pull16().wrapping_add(1);
+    }
+
+    fn lda_abs(cpu: &mut CPU) -> Op
+    {
+        Op::new(cpu, _lda, AddressMode::Abs)
+    }
+
+    fn _lda(cpu: &mut CPU, arg: &mut Value)
+    {
+        let val = arg.get(cpu);
+        cpu.registers.A = val;
+        cpu.registers.set_flag(StatusFlags::Z, val == 0);
+        cpu.registers.set_flag(StatusFlags::N, val & 0b10000000 > 0);
+    }
+
+    fn sta_zp(cpu: &mut CPU) -> Op
+    {
+        Op::new(cpu, _sta, AddressMode::Zp)
+    }
+
+    fn sta_zpx(cpu: &mut CPU) -> Op
+    {
+        Op::new(cpu, _sta, AddressMode::Zpx)
+    }
+
+    fn sta_abs(cpu: &mut CPU) -> Op
+    {
+        Op::new(cpu, _sta, AddressMode::Abs)
+    }
+
+    fn _sta(cpu: &mut CPU, arg: &mut Value)
+    {
+        arg.set(cpu, cpu.registers.A);
+    }
+
+    fn adc_imm(cpu: &mut CPU) -> Op
+    {      
+        Op::new(cpu, _adc, AddressMode::Imm)
+    }
+
+    fn adc_zp(cpu: &mut CPU) -> Op
+    {        
+        Op::new(cpu, _adc, AddressMode::Zp)
+    }
+
+    fn adc_zpx(cpu: &mut CPU) -> Op
+    {        
+        Op::new(cpu, _adc, AddressMode::Zpx)
+    }
+
+    fn _adc(cpu: &mut CPU, arg: &mut Value)
+    {
+        let base = cpu.registers.A as u16;
+        let operand = arg.get(cpu) as u16;
+        let mut result: u16 = base + operand;
+        if cpu.registers.get_flag(StatusFlags::C) {
+            result += 1;
+        }        
+
+        cpu.registers.set_flag(StatusFlags::C, result > 0xFF);
+        
+        let operand = operand as u8;
+        let result: u8 = result as u8;
+        cpu.registers.set_flag(StatusFlags::Z, result == 0);
+        cpu.registers.set_flag(StatusFlags::V, (operand ^ result) & (result ^ cpu.registers.A) & 0x80 != 0);
+        cpu.registers.set_flag(StatusFlags::N, result & 0b10000000 > 0);
+
+        cpu.registers.A = result;
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::bus::Bus;
+    use super::CPU;
+
+    fn load_program(program: Vec<u8>) -> CPU
+    {
+        let mut bus = Bus::new();
+        bus.write_buffer(0, &program);
+        CPU::new(Box::new(bus))
+    }
+
+    mod fault
+    {
+        #[cfg(feature = "ppu")]
+        use crate::bus::Bus;
+        use crate::bus::flat::Flat;
+        use crate::cpu::{tests::load_program, CPU, EmulationFault, FaultMode, StatusFlags};
+
+        #[test]
+        fn unknown_opcode_strict()
+        {
+            let mut cpu = load_program(vec![0xEA, 0x03]);
+
+            assert_eq!(Ok(()), cpu.ticks(1));
+            assert_eq!(Err(EmulationFault::UnknownOpcode { pc: 1, op_code: 0x03 }), cpu.tick());
+        }
+
+        #[test]
+        fn unknown_opcode_lenient()
+        {
+            let mut cpu = load_program(vec![0x03, 0x69, 0x02]);
+            cpu.set_fault_mode(FaultMode::Lenient);
+
+            assert_eq!(Ok(()), cpu.ticks(3));
+            assert_eq!(cpu.registers.A, 2);
+        }
+
+        #[test]
+        fn bus_fault_strict()
+        {
+            let mut cpu = load_program(vec![]);
+            cpu.registers.PC = 0x8000;
+
+            assert_eq!(Err(EmulationFault::BusFault(0x8000)), cpu.tick());
+        }
+
+        #[test]
+        fn bus_fault_lenient()
+        {
+            let mut cpu = load_program(vec![]);
+            cpu.set_fault_mode(FaultMode::Lenient);
+            cpu.registers.PC = 0x8000;
+
+            assert_eq!(Ok(()), cpu.ticks(4));
+        }
+
+        #[test]
+        fn kil_halts_cpu()
+        {
+            let mut cpu = load_program(vec![0xEA, 0x12, 0x69, 0x02]);
+
+            assert_eq!(Ok(()), cpu.ticks(1));
+            assert_eq!(Err(EmulationFault::Jammed { pc: 1, op_code: 0x12 }), cpu.tick());
+            assert!(cpu.is_halted());
+
+            assert_eq!(Ok(()), cpu.ticks(2));
+            assert_eq!(cpu.registers.A, 0);
+        }
+
+        #[test]
+        fn kil_halts_cpu_lenient()
+        {
+            let mut cpu = load_program(vec![0x02, 0x69, 0x02]);
+            cpu.set_fault_mode(FaultMode::Lenient);
+
+            assert_eq!(Ok(()), cpu.ticks(3));
+            assert!(cpu.is_halted());
+            assert_eq!(cpu.registers.A, 0);
+        }
+
+        #[test]
+        fn infinite_loop_detected()
+        {
+            let mut cpu = load_program(vec![0xEA, 0x4C, 0x01, 0x00]);
+            cpu.set_loop_detection(true);
+
+            assert_eq!(Ok(()), cpu.ticks(3));
+            assert_eq!(Err(EmulationFault::InfiniteLoop(1)), cpu.tick());
+        }
+
+        #[test]
+        fn infinite_loop_lenient()
+        {
+            let mut cpu = load_program(vec![0x4C, 0x00, 0x00]);
+            cpu.set_fault_mode(FaultMode::Lenient);
+            cpu.set_loop_detection(true);
+
+            assert_eq!(Some(EmulationFault::InfiniteLoop(0)), cpu.run_cycles(10).fault);
+            assert!(cpu.take_crash_report().is_some());
+        }
+
+        #[test]
+        fn wait_for_irq_not_a_loop()
+        {
+            let mut mem = Flat::new(vec![0; 0x10000]);
+            mem.mem[..3].copy_from_slice(&[0x4C, 0x00, 0x00]);
+            mem.irq = true;
+            let mut cpu = CPU::new(Box::new(mem));
+            cpu.set_loop_detection(true);
+            cpu.registers.set_flag(StatusFlags::I, true);
+            cpu.tick().unwrap();
+
+            // The IRQ is unmasked while JMP runs, so it's taken right after
+            cpu.registers.set_flag(StatusFlags::I, false);
+            assert_eq!(Ok(()), cpu.ticks(2));
+        }
+
+        #[cfg(feature = "ppu")]
+        #[test]
+        fn wait_for_nmi_not_a_loop()
+        {
+            let mut bus = Bus::new();
+            bus.get_ppu().set_warm_up(false);
+            bus.write8(0x2000, 0x80);
+            bus.write_buffer(0, &[0x4C, 0x00, 0x00]);
+            let mut cpu = CPU::new(Box::new(bus));
+            cpu.set_loop_detection(true);
+
+            assert_eq!(Ok(()), cpu.ticks(30));
+        }
+
+        #[test]
+        fn infinite_loop_not_detected_by_default()
+        {
+            let mut cpu = load_program(vec![0x4C, 0x00, 0x00]);
+
+            assert_eq!(Ok(()), cpu.ticks(9));
+        }
+    }
+
+    #[cfg(feature = "debugger")]
+    mod expr
+    {
+        use crate::cpu::tests::load_program;
+        use crate::debugger::expr::Expr;
+
+        #[test]
+        fn watch_cpu_state()
+        {
+            let mut cpu = load_program(vec![0x69, 0x05]);
+            cpu.ticks(2).unwrap();
+
+            assert!(Expr::parse("A == 5 && PC == 2 && [1] == 5").unwrap().is_true(&cpu).unwrap());
+        }
+    }
+
+    mod step
+    {
+        use crate::cpu::tests::load_program;
+        #[cfg(feature = "debugger")]
+        use crate::cpu::CPU;
+        #[cfg(feature = "debugger")]
+        use crate::debugger::breakpoint::{Breakpoint, Breakpoints, Trigger};
+        #[cfg(feature = "debugger")]
+        use crate::debugger::expr::Expr;
+
+        #[test]
+        fn step()
+        {
+            let mut cpu = load_program(vec![0x69, 0x02, 0x4C, 0x00, 0x00]);
+
+            cpu.step().unwrap();
+            assert_eq!(cpu.registers.PC, 2);
+            assert_eq!(cpu.cycle, 2);
+
+            cpu.step().unwrap();
+            assert_eq!(cpu.registers.PC, 0);
+            assert_eq!(cpu.cycle, 5);
+        }
+
+        #[cfg(feature = "debugger")]
+        #[test]
+        fn break_on_condition()
+        {
+            let mut cpu = load_program(vec![0x69, 0x02, 0x4C, 0x00, 0x00]);
+            let mut bps = Breakpoints::new();
+            let id = bps.add(Breakpoint::new(0x0002).with_condition(Expr::parse("A >= 6").unwrap()));
+
+            while bps.check(&cpu).unwrap().is_none() {
+                cpu.step().unwrap();
+            }
+
+            assert_eq!(cpu.registers.A, 6);
+            assert_eq!(1, bps.get(id).unwrap().get_hits());
+        }
+
+        #[cfg(feature = "debugger")]
+        #[test]
+        fn break_on_mirrored_access()
+        {
+            // LDA $0800; STA $1800
+            let mut cpu = load_program(vec![0xAD, 0x00, 0x08, 0x8D, 0x00, 0x18]);
+            let mut bps = Breakpoints::new();
+            let read = bps.add(Breakpoint::new(0x0000).with_trigger(Trigger::Read));
+            let write = bps.add(Breakpoint::new(0x0000).with_trigger(Trigger::Write));
+            cpu.set_watchpoints(&bps.get_watchpoints());
+
+            let mut step = |cpu: &mut CPU| {
+                cpu.step().unwrap();
+                let hits = cpu.take_watch_hits();
+                hits.iter().find_map(|access| bps.check_access(access, cpu).unwrap())
+            };
+
+            assert_eq!(Some(read), step(&mut cpu));
+            assert_eq!(0xAD, cpu.registers.A);
+            assert_eq!(Some(write), step(&mut cpu));
+            assert_eq!(0xAD, cpu.bus.peek8(0x0000));
+        }
+    }
+
+    mod journal
+    {
+        use crate::cpu::tests::load_program;
+
+        #[test]
+        fn step_back()
+        {
+            let mut cpu = load_program(vec![0x69, 0x02, 0x69, 0x03]);
+            cpu.set_journal_capacity(16);
+            cpu.step().unwrap();
+            cpu.step().unwrap();
+            assert_eq!(cpu.registers.A, 5);
+
+            assert!(cpu.step_back());
+            assert_eq!(cpu.registers.A, 2);
+            assert_eq!(cpu.registers.PC, 2);
+            assert_eq!(cpu.cycle, 2);
+
+            assert!(cpu.step_back());
+            assert_eq!(cpu.registers.A, 0);
+            assert_eq!(cpu.registers.PC, 0);
+            assert!(!cpu.step_back());
+
+            cpu.step().unwrap();
+            assert_eq!(cpu.registers.A, 2);
+        }
+
+        #[test]
+        fn step_back_restores_ram()
+        {
+            let mut cpu = load_program(vec![0xEA, 0xEA]);
+            cpu.set_journal_capacity(16);
+            cpu.step().unwrap();
+            cpu.bus.write8(0x10, 42);
+            cpu.bus.write8(0x10, 43);
+            cpu.step().unwrap();
+
+            assert!(cpu.step_back());
+            assert_eq!(cpu.bus.peek8(0x10), 43);
+            assert!(cpu.step_back());
+            assert_eq!(cpu.bus.peek8(0x10), 0);
+        }
+
+        #[test]
+        fn journal_is_bounded()
+        {
+            let mut cpu = load_program(vec![0xEA, 0xEA, 0xEA]);
+            cpu.set_journal_capacity(2);
+            cpu.ticks(3).unwrap();
+
+            assert!(cpu.step_back());
+            assert!(cpu.step_back());
+            assert!(!cpu.step_back());
+            assert_eq!(cpu.registers.PC, 1);
+        }
+
+        #[test]
+        fn journal_disabled()
+        {
+            let mut cpu = load_program(vec![0xEA]);
+            cpu.step().unwrap();
+
+            assert!(!cpu.step_back());
+        }
+    }
+
+    mod custom_bus
+    {
+        use crate::bus::flat::Flat;
+        use crate::cpu::CPU;
+
+        #[test]
+        fn run_from_high_memory()
+        {
+            let mut mem = Flat::new(vec![0; 0x10000]);
+            mem.mem[..3].copy_from_slice(&[0x4C, 0x00, 0xC0]);
+            mem.mem[0xC000..0xC002].copy_from_slice(&[0x69, 0x05]);
+
+            let mut cpu = CPU::new(Box::new(mem));
+            cpu.ticks(5).unwrap();
+
+            assert_eq!(cpu.registers.PC, 0xC002);
+            assert_eq!(cpu.registers.A, 5);
+        }
+    }
+
+    mod interrupt
+    {
+        use crate::bus::flat::Flat;
+        use crate::cpu::{CPU, Flags, StatusFlags};
+
+        fn load(irq: bool, nmi: bool) -> CPU
+        {
+            let mut mem = vec![0xEA; 0x10000];
+            mem[0xFFFA..].copy_from_slice(&[0x00, 0x90, 0x00, 0x00, 0x00, 0xA0]);
+            let mut cpu = CPU::new(Box::new(Flat { mem, irq, nmi }));
+            cpu.registers.PC = 0x1234;
+            cpu.registers.SP = 0xFD;
+            cpu
+        }
+
+        #[test]
+        fn nmi()
+        {
+            let mut cpu = load(true, true);
+            cpu.registers.PS = Flags::from_bits(StatusFlags::C as u8 | StatusFlags::B as u8);
+            cpu.ticks(7).unwrap();
+
+            assert_eq!(cpu.registers.PC, 0x9000);
+            assert_eq!(cpu.registers.SP, 0xFA);
+            assert!(cpu.registers.get_flag(StatusFlags::I));
+            assert_eq!(cpu.bus.peek8(0x1FD), 0x12);
+            assert_eq!(cpu.bus.peek8(0x1FC), 0x34);
+            assert_eq!(cpu.bus.peek8(0x1FB), 0b00100001);
+        }
+
+        #[test]
+        fn irq()
+        {
+            let mut cpu = load(true, false);
+            cpu.ticks(7).unwrap();
+
+            assert_eq!(cpu.registers.PC, 0xA000);
+        }
+
+        #[test]
+        fn irq_masked()
+        {
+            let mut cpu = load(true, false);
+            cpu.registers.set_flag(StatusFlags::I, true);
+            cpu.step().unwrap();
+
+            assert_eq!(cpu.registers.PC, 0x1235);
+        }
+    }
+
+    mod run_cycles
+    {
+        use crate::cpu::{tests::load_program, CycleRun, EmulationFault};
+
+        #[test]
+        fn resumes_instruction()
+        {
+            let mut cpu = load_program(vec![0x4C, 0x34, 0x12]);
+
+            assert_eq!(CycleRun { ran: 2, fault: None }, cpu.run_cycles(2));
+            assert_eq!(cpu.registers.PC, 3);
+            assert_eq!(CycleRun { ran: 1, fault: None }, cpu.run_cycles(1));
+            assert_eq!(cpu.registers.PC, 0x1234);
+            assert_eq!(3, cpu.get_cycle());
+        }
+
+        #[test]
+        fn stops_on_unknown_opcode()
+        {
+            let mut cpu = load_program(vec![0xEA, 0x03]);
+            let run = cpu.run_cycles(10);
+
+            assert_eq!(2, run.ran);
+            assert_eq!(Some(EmulationFault::UnknownOpcode { pc: 1, op_code: 0x03 }), run.fault);
+            assert_eq!(2, cpu.get_cycle());
+        }
+
+        #[test]
+        fn stops_on_kil()
+        {
+            let mut cpu = load_program(vec![0x69, 0x01, 0xEA, 0x02]);
+            let run = cpu.run_cycles(100);
+
+            assert_eq!(4, run.ran);
+            assert_eq!(Some(EmulationFault::Jammed { pc: 3, op_code: 0x02 }), run.fault);
+            assert_eq!(4, cpu.get_cycle());
+        }
+    }
+
+    mod registers
+    {
+        use crate::cpu::{tests::load_program, Flags, StatusFlags};
+
+        #[test]
+        fn flags_display()
+        {
+            let mut flags = Flags::from_bits(0b00100000);
+            flags.set(StatusFlags::N, true);
+            flags.set(StatusFlags::C, true);
+
+            assert_eq!("Nv-bdizC", flags.to_string());
+            assert_eq!("Flags(Nv-bdizC)", format!("{:?}", flags));
+            assert_eq!(0b10100001, flags.bits());
+        }
+
+        #[test]
+        fn get_registers()
+        {
+            let mut cpu = load_program(vec![0x69, 0x80]);
+            cpu.ticks(2).unwrap();
+            let registers = cpu.get_registers();
+
+            assert_eq!(0x80, registers.a);
+            assert_eq!(2, registers.pc);
+            assert!(registers.ps.get(StatusFlags::N));
+        }
+
+        #[test]
+        fn set_registers()
+        {
+            let mut cpu = load_program(vec![0x75, 0xF0]);
+            cpu.bus.write8(0x0010, 0x42);
+            let mut registers = cpu.get_registers();
+            registers.x = 0x20;
+            cpu.set_registers(registers);
+            cpu.step().unwrap();
+
+            assert_eq!(0x42, cpu.get_registers().a);
+        }
+    }
+
+    mod crash_report
+    {
+        use crate::cpu::{tests::load_program, EmulationFault, FaultMode};
+
+        #[test]
+        fn report_on_fault()
+        {
+            let mut cpu = load_program(vec![0xEA, 0xEA, 0xEA, 0x03]);
+            cpu.set_trace_capacity(2);
+            cpu.bus.write8(0x1FF, 0x42);
+
+            assert!(cpu.ticks(4).is_err());
+            let report = cpu.take_crash_report().unwrap();
+
+            assert_eq!(EmulationFault::UnknownOpcode { pc: 3, op_code: 0x03 }, report.fault);
+            assert_eq!(4, report.registers.pc);
+            assert_eq!(vec![2, 3], report.trace.iter().map(|line| line.registers.pc).collect::<Vec<_>>());
+            assert_eq!(0x42, report.stack[0xFF]);
+            assert!(report.to_string().contains("0003  03  A:00 X:00 Y:00 P:nv-bdizc SP:00 CYC:3"));
+            assert!(cpu.take_crash_report().is_none());
+        }
+
+        #[test]
+        fn no_report_when_lenient()
+        {
+            let mut cpu = load_program(vec![0x03]);
+            cpu.set_fault_mode(FaultMode::Lenient);
+            cpu.ticks(1).unwrap();
+
+            assert!(cpu.take_crash_report().is_none());
+        }
+    }
+
+    mod jmp
+    {
+        use crate::cpu::tests::load_program;
+
+        #[test]
+        fn jmp_abs()
+        {
+            let mut cpu = load_program(vec![0x4C, 0x34, 0x12]);
+            cpu.ticks(3).unwrap();
+            assert_eq!(cpu.registers.PC, 0x1234);
+        }
+    }
+
+    mod jsr
+    {
+        use crate::cpu::tests::load_program;
+
+        #[test]
+        fn jsr_abs()
+        {
+            let mut cpu = load_program(vec![0x20, 0x34, 0x12]);
+            cpu.registers.SP = 0xFD;
+            cpu.ticks(6).unwrap();
+
+            assert_eq!(0x1234, cpu.registers.PC);
+            assert_eq!(0xFB, cpu.registers.SP);
+            assert_eq!(0x00, cpu.peek8(0x1FD));
+            assert_eq!(0x02, cpu.peek8(0x1FC));
+        }
+
+        #[test]
+        fn rts_returns_after_jsr()
+        {
+            // JSR $0010, KIL ... $0010: RTS
+            let mut program = vec![0x20, 0x10, 0x00, 0xEA];
+            program.resize(0x10, 0x02);
+            program.push(0x60);
+            let mut cpu = load_program(program);
+            cpu.registers.SP = 0xFD;
+            cpu.ticks(6 + 6).unwrap();
+
+            assert_eq!(0x0003, cpu.registers.PC);
+            assert_eq!(0xFD, cpu.registers.SP);
+        }
+    }
+
+    mod adc
+    {
+        use std::vec;
+        use crate::cpu::{tests::load_program, StatusFlags};
+
+        #[test]
+        fn adc_imm()
+        {
+            let mut cpu = load_program(vec![0x69, 0x02]);
+            cpu.ticks(2).unwrap();
+            assert_eq!(cpu.registers.A, 2);
+        }
+
+        #[test]
+        fn adc_imm_multiple()
+        {
+            let mut cpu = load_program(vec![
+                0x69, 0x02,
+                0x69, 0x03,
+                0x69, 0x04
+            ]);
+
+            cpu.ticks(6).unwrap();
+
+            assert_eq!(cpu.registers.A, 9);
+        }
+
+        #[test]
+        fn adc_imm_z_flag_set()
+        {
+            let mut cpu = load_program(vec![
+                0x69, 0xFF,
+                0x69, 0x01
+            ]);
+
+            cpu.ticks(4).unwrap();
+
+            assert!(cpu.registers.get_flag(StatusFlags::Z));
+        }
+
+        #[test]
+        fn adc_imm_z_flag_unset()
+        {
+            let mut cpu = load_program(vec![
+                0x69, 0x01,
+                0x69, 0x02
+            ]);
+
+            cpu.ticks(4).unwrap();
+
+            assert!(!cpu.registers.get_flag(StatusFlags::Z));
         }
 
         #[test]
@@ -374,7 +1557,7 @@ mod tests
                 0x69, 0x01
             ]);
   
-            cpu.ticks(4);
+            cpu.ticks(4).unwrap();
 
             assert_eq!(cpu.registers.A as i8, -128);
             assert!(cpu.registers.get_flag(StatusFlags::V));
@@ -388,7 +1571,7 @@ mod tests
                 0x69, 0x02
             ]);
   
-            cpu.ticks(4);
+            cpu.ticks(4).unwrap();
 
             assert!(!cpu.registers.get_flag(StatusFlags::V));
         }  
@@ -401,7 +1584,7 @@ mod tests
                 0x69, 0x02
             ]);
             
-            cpu.ticks(4);
+            cpu.ticks(4).unwrap();
 
             assert!(cpu.registers.get_flag(StatusFlags::N));            
         }
@@ -414,9 +1597,33 @@ mod tests
                 0x69, 0x02
             ]);
 
-            cpu.ticks(4);
+            cpu.ticks(4).unwrap();
 
             assert!(!cpu.registers.get_flag(StatusFlags::N));
         }
     }    
+
+    mod sta
+    {
+        use crate::cpu::tests::load_program;
+
+        #[test]
+        fn sta_zp()
+        {
+            let mut cpu = load_program(vec![0x69, 0x42, 0x85, 0x10]);
+            cpu.ticks(5).unwrap();
+
+            assert_eq!(0x42, cpu.peek8(0x10));
+        }
+
+        #[test]
+        fn sta_zpx_wraps()
+        {
+            let mut cpu = load_program(vec![0x69, 0x42, 0x95, 0xF0]);
+            cpu.registers.X = 0x20;
+            cpu.ticks(6).unwrap();
+
+            assert_eq!(0x42, cpu.peek8(0x10));
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,320 @@
+use super::expr::{Expr, ExprContext, ExprError, Register};
+use super::memory_map;
+use crate::bus::Access;
+
+// What a breakpoint reacts to. Read and write breakpoints are matched against
+// the accesses the bus records for Breakpoints::get_watchpoints().
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger
+{
+    Execute,
+    Read,
+    Write,
+    // Either a read or a write
+    Access
+}
+
+pub struct Breakpoint
+{
+    addr: u16,
+    trigger: Trigger,
+    condition: Option<Expr>,
+    hit_count: u32,
+    temporary: bool,
+    hits: u32
+}
+
+impl Breakpoint
+{
+    pub fn new(addr: u16) -> Breakpoint
+    {
+        Breakpoint {
+            addr,
+            trigger: Trigger::Execute,
+            condition: None,
+            hit_count: 1,
+            temporary: false,
+            hits: 0
+        }
+    }
+
+    pub fn with_trigger(mut self, trigger: Trigger) -> Breakpoint
+    {
+        self.trigger = trigger;
+        self
+    }
+
+    // Only counts a hit when the expression evaluates to non-zero
+    pub fn with_condition(mut self, condition: Expr) -> Breakpoint
+    {
+        self.condition = Some(condition);
+        self
+    }
+
+    // Breaks on the Nth hit and every hit after it
+    pub fn with_hit_count(mut self, hit_count: u32) -> Breakpoint
+    {
+        self.hit_count = hit_count;
+        self
+    }
+
+    // Removed as soon as it breaks
+    pub fn temporary(mut self) -> Breakpoint
+    {
+        self.temporary = true;
+        self
+    }
+
+    pub fn get_addr(&self) -> u16
+    {
+        self.addr
+    }
+
+    pub fn get_trigger(&self) -> Trigger
+    {
+        self.trigger
+    }
+
+    pub fn get_hits(&self) -> u32
+    {
+        self.hits
+    }
+
+    // Mirrors of the breakpoint address match as well
+    fn matches(&self, addr: u16) -> bool
+    {
+        memory_map::canonicalize(self.addr) == memory_map::canonicalize(addr)
+    }
+
+    fn is_condition_met(&self, ctx: &dyn ExprContext) -> Result<bool, ExprError>
+    {
+        match &self.condition {
+            Some(condition) => condition.is_true(ctx),
+            None => Ok(true)
+        }
+    }
+
+    // Returns whether the hit count has been reached
+    fn record_hit(&mut self) -> bool
+    {
+        self.hits += 1;
+        self.hits >= self.hit_count
+    }
+}
+
+#[derive(Default)]
+pub struct Breakpoints
+{
+    list: Vec<(usize, Breakpoint)>,
+    next_id: usize
+}
+
+impl Breakpoints
+{
+    pub fn new() -> Breakpoints
+    {
+        Breakpoints::default()
+    }
+
+    pub fn add(&mut self, breakpoint: Breakpoint) -> usize
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.list.push((id, breakpoint));
+        id
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Breakpoint>
+    {
+        let index = self.list.iter().position(|(bp_id, _)| *bp_id == id)?;
+        Some(self.list.remove(index).1)
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Breakpoint>
+    {
+        self.list.iter().find(|(bp_id, _)| *bp_id == id).map(|(_, bp)| bp)
+    }
+
+    // Call before executing the instruction at PC. Returns the id of the first
+    // execution breakpoint that triggered, every one at PC or one of its
+    // mirrors has its hit recorded. If a condition fails to evaluate nothing
+    // is recorded, so checking again behaves the same.
+    pub fn check(&mut self, ctx: &dyn ExprContext) -> Result<Option<usize>, ExprError>
+    {
+        let pc = ctx.get_register(Register::PC);
+        self.check_matching(ctx, |bp| bp.trigger == Trigger::Execute && bp.matches(pc))
+    }
+
+    // Same as check() for read and write breakpoints, call with every access
+    // the bus recorded for the addresses from get_watchpoints()
+    pub fn check_access(&mut self, access: &Access, ctx: &dyn ExprContext) -> Result<Option<usize>, ExprError>
+    {
+        let trigger = if access.write { Trigger::Write } else { Trigger::Read };
+        self.check_matching(ctx, |bp| (bp.trigger == trigger || bp.trigger == Trigger::Access) && bp.matches(access.addr))
+    }
+
+    // Addresses the bus has to watch for the read and write breakpoints
+    pub fn get_watchpoints(&self) -> Vec<u16>
+    {
+        let mut addrs: Vec<u16> = self.list.iter()
+            .filter(|(_, bp)| bp.trigger != Trigger::Execute)
+            .map(|(_, bp)| memory_map::canonicalize(bp.addr))
+            .collect();
+        addrs.sort();
+        addrs.dedup();
+        addrs
+    }
+
+    fn check_matching(&mut self, ctx: &dyn ExprContext, matches: impl Fn(&Breakpoint) -> bool) -> Result<Option<usize>, ExprError>
+    {
+        let mut matched = Vec::new();
+        for (i, (_, bp)) in self.list.iter().enumerate() {
+            if matches(bp) && bp.is_condition_met(ctx)? {
+                matched.push(i);
+            }
+        }
+
+        let mut triggered = None;
+        for i in matched {
+            let (id, bp) = &mut self.list[i];
+            if bp.record_hit() && triggered.is_none() {
+                triggered = Some(*id);
+            }
+        }
+
+        if let Some(id) = triggered {
+            if self.get(id).is_some_and(|bp| bp.temporary) {
+                self.remove(id);
+            }
+        }
+
+        Ok(triggered)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    struct TestContext
+    {
+        pc: u16,
+        a: u16
+    }
+
+    impl ExprContext for TestContext
+    {
+        fn get_register(&self, reg: Register) -> u16
+        {
+            match reg {
+                Register::PC => self.pc,
+                Register::A => self.a,
+                _ => 0
+            }
+        }
+
+        fn peek8(&self, _addr: u16) -> u8
+        {
+            0
+        }
+    }
+
+    #[test]
+    fn address()
+    {
+        let mut bps = Breakpoints::new();
+        let id = bps.add(Breakpoint::new(0x8000));
+
+        assert_eq!(None, bps.check(&TestContext { pc: 0x8001, a: 0 }).unwrap());
+        assert_eq!(Some(id), bps.check(&TestContext { pc: 0x8000, a: 0 }).unwrap());
+        assert_eq!(Some(id), bps.check(&TestContext { pc: 0x8000, a: 0 }).unwrap());
+    }
+
+    #[test]
+    fn ram_mirror()
+    {
+        let mut bps = Breakpoints::new();
+        let id = bps.add(Breakpoint::new(0x0000));
+
+        assert_eq!(Some(id), bps.check(&TestContext { pc: 0x0800, a: 0 }).unwrap());
+        assert_eq!(Some(id), bps.check(&TestContext { pc: 0x1800, a: 0 }).unwrap());
+        assert_eq!(None, bps.check(&TestContext { pc: 0x0801, a: 0 }).unwrap());
+    }
+
+    #[test]
+    fn access_mirror()
+    {
+        let mut bps = Breakpoints::new();
+        let read = bps.add(Breakpoint::new(0x0000).with_trigger(Trigger::Read));
+        let write = bps.add(Breakpoint::new(0x0000).with_trigger(Trigger::Write));
+        let any = bps.add(Breakpoint::new(0x1000).with_trigger(Trigger::Access));
+        let ctx = TestContext { pc: 0x8000, a: 0 };
+        let access = |addr, write| Access { cycle: 0, addr, val: 0, write };
+
+        assert_eq!(vec![0x0000], bps.get_watchpoints());
+        assert_eq!(Some(read), bps.check_access(&access(0x0800, false), &ctx).unwrap());
+        assert_eq!(Some(write), bps.check_access(&access(0x1800, true), &ctx).unwrap());
+        assert_eq!(2, bps.get(any).unwrap().get_hits());
+        assert_eq!(None, bps.check_access(&access(0x0001, true), &ctx).unwrap());
+        assert_eq!(None, bps.check(&TestContext { pc: 0x0000, a: 0 }).unwrap());
+    }
+
+    #[test]
+    fn condition()
+    {
+        let mut bps = Breakpoints::new();
+        let id = bps.add(Breakpoint::new(0x8000).with_condition(Expr::parse("A > 5").unwrap()));
+
+        assert_eq!(None, bps.check(&TestContext { pc: 0x8000, a: 5 }).unwrap());
+        assert_eq!(Some(id), bps.check(&TestContext { pc: 0x8000, a: 6 }).unwrap());
+        assert_eq!(1, bps.get(id).unwrap().get_hits());
+    }
+
+    #[test]
+    fn condition_error_records_nothing()
+    {
+        let mut bps = Breakpoints::new();
+        let first = bps.add(Breakpoint::new(0x8000).temporary());
+        bps.add(Breakpoint::new(0x8000).with_condition(Expr::parse("1 / A").unwrap()));
+
+        assert!(bps.check(&TestContext { pc: 0x8000, a: 0 }).is_err());
+        assert_eq!(0, bps.get(first).unwrap().get_hits());
+        assert_eq!(Some(first), bps.check(&TestContext { pc: 0x8000, a: 1 }).unwrap());
+    }
+
+    #[test]
+    fn hit_count()
+    {
+        let mut bps = Breakpoints::new();
+        let id = bps.add(Breakpoint::new(0x8000).with_hit_count(3));
+        let ctx = TestContext { pc: 0x8000, a: 0 };
+
+        assert_eq!(None, bps.check(&ctx).unwrap());
+        assert_eq!(None, bps.check(&ctx).unwrap());
+        assert_eq!(Some(id), bps.check(&ctx).unwrap());
+        assert_eq!(Some(id), bps.check(&ctx).unwrap());
+    }
+
+    #[test]
+    fn temporary()
+    {
+        let mut bps = Breakpoints::new();
+        let id = bps.add(Breakpoint::new(0x8000).temporary());
+        let ctx = TestContext { pc: 0x8000, a: 0 };
+
+        assert_eq!(Some(id), bps.check(&ctx).unwrap());
+        assert_eq!(None, bps.check(&ctx).unwrap());
+        assert!(bps.get(id).is_none());
+    }
+
+    #[test]
+    fn remove()
+    {
+        let mut bps = Breakpoints::new();
+        let id = bps.add(Breakpoint::new(0x8000));
+
+        assert_eq!(0x8000, bps.remove(id).unwrap().get_addr());
+        assert_eq!(None, bps.check(&TestContext { pc: 0x8000, a: 0 }).unwrap());
+    }
+}
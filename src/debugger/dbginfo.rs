@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+pub use self::error::DbgInfoError;
+
+mod error
+{
+    use std::{fmt::Display, error::Error};
+
+    #[derive(Debug)]
+    pub struct DbgInfoError(pub String);
+
+    impl Display for DbgInfoError
+    {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+        {
+            write!(f, "Debug info error: {}", self.0)
+        }
+    }
+
+    impl Error for DbgInfoError {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceLine<'a>
+{
+    pub file: &'a str,
+    pub line: u32
+}
+
+// CPU address range generated by one source line
+#[derive(Debug, Clone, Copy)]
+struct LineSpan
+{
+    start: u16,
+    end: u16,
+    file: usize,
+    line: u32
+}
+
+struct Segment
+{
+    start: u32
+}
+
+struct Span
+{
+    seg: usize,
+    start: u32,
+    size: u32
+}
+
+// Source-level debug info from a cc65/ld65 .dbg file (ld65 --dbgfile)
+#[derive(Default)]
+pub struct DebugInfo
+{
+    files: HashMap<usize, String>,
+    lines: Vec<LineSpan>,
+    labels: HashMap<String, u16>
+}
+
+impl DebugInfo
+{
+    pub fn parse(source: &str) -> Result<DebugInfo, DbgInfoError>
+    {
+        let mut info = DebugInfo::default();
+        let mut segments = HashMap::new();
+        let mut spans = HashMap::new();
+        let mut lines = Vec::new();
+
+        for (i, record) in source.lines().enumerate() {
+            let err = |msg: &str| DbgInfoError(format!("{} on line {}", msg, i + 1));
+            let Some((kind, fields)) = record.split_once(char::is_whitespace) else {
+                continue;
+            };
+            let fields = parse_fields(fields).ok_or_else(|| err("Malformed record"))?;
+            let get = |key: &str| fields.get(key).copied().ok_or_else(|| err(&format!("Missing {}", key)));
+            let num = |key: &str| get(key).and_then(|val| parse_number(val).ok_or_else(|| err(&format!("Invalid {}", key))));
+
+            match kind {
+                "file" => {
+                    info.files.insert(num("id")? as usize, get("name")?.to_string());
+                },
+                "seg" => {
+                    segments.insert(num("id")? as usize, Segment { start: num("start")? });
+                },
+                "span" => {
+                    spans.insert(num("id")? as usize, Span { seg: num("seg")? as usize, start: num("start")?, size: num("size")? });
+                },
+                "line" => {
+                    // Lines without a span produced no code
+                    if let Some(span_ids) = fields.get("span") {
+                        lines.push((num("file")? as usize, num("line")?, span_ids.to_string()));
+                    }
+                },
+                // Only labels have a CPU address, equates and imports are skipped
+                "sym" if fields.get("type") == Some(&"lab") => {
+                    if let Some(val) = fields.get("val").and_then(|val| parse_number(val)) {
+                        let addr = u16::try_from(val).map_err(|_| err("Label outside the CPU address space"))?;
+                        info.labels.insert(get("name")?.to_string(), addr);
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        for (file, line, span_ids) in lines {
+            for id in span_ids.split('+') {
+                let span = parse_number(id)
+                    .and_then(|id| spans.get(&(id as usize)))
+                    .ok_or_else(|| DbgInfoError(format!("Unknown span {}", id)))?;
+                let seg = segments.get(&span.seg)
+                    .ok_or_else(|| DbgInfoError(format!("Unknown segment {}", span.seg)))?;
+                if span.size == 0 {
+                    continue;
+                }
+
+                // The values come straight from the file, so a span may run
+                // past the end of the CPU address space
+                let addr = |offset: u32| seg.start.checked_add(offset).and_then(|addr| u16::try_from(addr).ok());
+                let end = span.start.checked_add(span.size - 1).and_then(addr);
+                let (Some(start), Some(end)) = (addr(span.start), end) else {
+                    return Err(DbgInfoError(format!("Span {} is outside the CPU address space", id)));
+                };
+                info.lines.push(LineSpan { start, end, file, line });
+            }
+        }
+
+        Ok(info)
+    }
+
+    // Source line that generated the code at addr. When spans overlap, e.g. a
+    // macro invocation and the macro body, the narrowest one wins.
+    pub fn get_source_line(&self, addr: u16) -> Option<SourceLine<'_>>
+    {
+        self.lines.iter()
+            .filter(|span| span.start <= addr && addr <= span.end)
+            .min_by_key(|span| span.end - span.start)
+            .and_then(|span| Some(SourceLine { file: self.files.get(&span.file)?, line: span.line }))
+    }
+
+    // Start addresses of the code generated by a source line, for setting
+    // breakpoints. The file matches by full name or by its last path component.
+    pub fn get_addresses(&self, file: &str, line: u32) -> Vec<u16>
+    {
+        let mut addrs: Vec<u16> = self.lines.iter()
+            .filter(|span| span.line == line)
+            .filter(|span| self.files.get(&span.file).is_some_and(|name| name == file || name.rsplit(['/', '\\']).next() == Some(file)))
+            .map(|span| span.start)
+            .collect();
+        addrs.sort();
+        addrs.dedup();
+        addrs
+    }
+
+    // Label addresses, to pass to Expr::parse_with_labels
+    pub fn get_labels(&self) -> &HashMap<String, u16>
+    {
+        &self.labels
+    }
+}
+
+// Splits key=value pairs, values may be quoted strings containing commas
+fn parse_fields(fields: &str) -> Option<HashMap<&str, &str>>
+{
+    let mut map = HashMap::new();
+    let mut rest = fields.trim();
+    while !rest.is_empty() {
+        let (key, value) = rest.split_once('=')?;
+        let (value, next) = if let Some(quoted) = value.strip_prefix('"') {
+            let end = quoted.find('"')?;
+            (&quoted[..end], &quoted[end + 1..])
+        }
+        else {
+            value.split_at(value.find(',').unwrap_or(value.len()))
+        };
+
+        map.insert(key, value);
+        rest = next.strip_prefix(',').unwrap_or(next);
+    }
+    Some(map)
+}
+
+fn parse_number(val: &str) -> Option<u32>
+{
+    match val.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => val.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{DebugInfo, SourceLine};
+    use crate::debugger::expr::Expr;
+
+    const DBG: &str = r#"version	major=2,minor=0
+info	csym=0,file=2,lib=0,line=4,mod=1,scope=1,seg=2,span=4,sym=3,type=4
+file	id=0,name="src/main.s",size=120,mtime=0x5F5E1000,mod=0
+file	id=1,name="src/macros, util.inc",size=40,mtime=0x5F5E1000,mod=0
+mod	id=0,name="main.o",file=0
+seg	id=0,name="CODE",start=0x008000,size=0x0010,addrsize=absolute,type=ro,oname="game.nes",ooffs=16
+seg	id=1,name="ZEROPAGE",start=0x000000,size=0x0002,addrsize=zeropage,type=rw
+span	id=0,seg=0,start=0,size=3
+span	id=1,seg=0,start=3,size=4
+span	id=2,seg=0,start=5,size=2
+span	id=3,seg=1,start=0,size=0
+line	id=0,file=0,line=10,span=0
+line	id=1,file=0,line=12,span=1
+line	id=2,file=1,line=3,type=2,span=2
+line	id=3,file=0,line=1
+sym	id=0,name="reset",addrsize=absolute,scope=0,def=0,ref=1,val=0x8000,seg=0,type=lab
+sym	id=1,name="loop",addrsize=absolute,scope=0,def=1,val=0x8003,seg=0,type=lab
+sym	id=2,name="SPEED",addrsize=zeropage,scope=0,def=2,val=0x4,type=equ
+"#;
+
+    #[test]
+    fn source_lines()
+    {
+        let info = DebugInfo::parse(DBG).unwrap();
+
+        assert_eq!(Some(SourceLine { file: "src/main.s", line: 10 }), info.get_source_line(0x8002));
+        assert_eq!(Some(SourceLine { file: "src/main.s", line: 12 }), info.get_source_line(0x8004));
+        assert_eq!(Some(SourceLine { file: "src/macros, util.inc", line: 3 }), info.get_source_line(0x8005));
+        assert_eq!(None, info.get_source_line(0x8007));
+    }
+
+    #[test]
+    fn addresses()
+    {
+        let info = DebugInfo::parse(DBG).unwrap();
+
+        assert_eq!(vec![0x8003], info.get_addresses("main.s", 12));
+        assert_eq!(vec![0x8000], info.get_addresses("src/main.s", 10));
+        assert!(info.get_addresses("main.s", 1).is_empty());
+    }
+
+    #[test]
+    fn labels()
+    {
+        let info = DebugInfo::parse(DBG).unwrap();
+
+        assert_eq!(2, info.get_labels().len());
+        assert_eq!(Some(&0x8003), info.get_labels().get("loop"));
+        assert!(Expr::parse_with_labels("PC == loop", info.get_labels()).is_ok());
+    }
+
+    #[test]
+    fn errors()
+    {
+        assert!(DebugInfo::parse("line\tid=0,file=0,line=1,span=9\n").is_err());
+        assert!(DebugInfo::parse("span\tid=0,seg=0,start=zz,size=1\n").is_err());
+        assert!(DebugInfo::parse("file\tid=0,name=\"unterminated\n").is_err());
+    }
+
+    #[test]
+    fn addresses_out_of_range()
+    {
+        let span = |seg_start: u32, start: u32, size: u32| DebugInfo::parse(&format!(
+            "seg\tid=0,start={}\nspan\tid=0,seg=0,start={},size={}\nline\tid=0,file=0,line=1,span=0\n",
+            seg_start, start, size));
+
+        assert!(span(0xFFFF, 0, 1).is_ok());
+        assert!(span(0xFFFF, 0, 2).is_err());
+        assert!(span(0xFFFF_FFFF, 1, 1).is_err());
+        assert!(span(0, 0xFFFF_FFFF, 2).is_err());
+        assert!(span(0x10000, 0, 1).is_err());
+        assert!(DebugInfo::parse("sym\tid=0,name=\"far\",val=0x10000,type=lab\n").is_err());
+    }
+}
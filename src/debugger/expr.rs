@@ -0,0 +1,454 @@
+use std::collections::HashMap;
+
+pub use self::error::ExprError;
+
+mod error
+{
+    use std::{fmt::Display, error::Error};
+
+    #[derive(Debug)]
+    pub struct ExprError(pub String);
+
+    impl Display for ExprError
+    {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+        {
+            write!(f, "Expression error: {}", self.0)
+        }
+    }
+
+    impl Error for ExprError {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register
+{
+    A,
+    X,
+    Y,
+    SP,
+    PC,
+    PS
+}
+
+// State an expression is evaluated against. Memory access must not have side
+// effects, so watching $2002 doesn't acknowledge VBlank.
+pub trait ExprContext
+{
+    fn get_register(&self, reg: Register) -> u16;
+    fn peek8(&self, addr: u16) -> u8;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnaryOp
+{
+    Neg,
+    Not,
+    BitNot
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp
+{
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem
+}
+
+// Binary operators from the lowest to the highest precedence level
+const PRECEDENCE: [&[(&str, BinaryOp)]; 8] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne), ("<=", BinaryOp::Le), (">=", BinaryOp::Ge), ("<", BinaryOp::Lt), (">", BinaryOp::Gt)],
+    &[("|", BinaryOp::BitOr)],
+    &[("^", BinaryOp::BitXor)],
+    &[("&", BinaryOp::BitAnd)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[("*", BinaryOp::Mul), ("/", BinaryOp::Div), ("%", BinaryOp::Rem)],
+];
+
+#[derive(Debug, Clone)]
+enum Node
+{
+    Number(i64),
+    Register(Register),
+    Memory(Box<Node>),
+    Unary(UnaryOp, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token
+{
+    Number(i64),
+    Ident(String),
+    Op(&'static str)
+}
+
+const OPERATORS: [&str; 22] = [
+    "||", "&&", "==", "!=", "<=", ">=",
+    "<", ">", "|", "^", "&", "+", "-", "*", "/", "%", "!", "~", "(", ")", "[", "]"
+];
+
+// Watch expression such as `[0x00FE] + X > 5`.
+//
+// Numbers are decimal, `0x` or `$` prefixed hex. `[addr]` reads a byte of
+// memory, A/X/Y/SP/PC/PS name CPU registers and any other identifier is
+// looked up in the label table.
+#[derive(Debug, Clone)]
+pub struct Expr
+{
+    root: Node
+}
+
+impl Expr
+{
+    pub fn parse(source: &str) -> Result<Expr, ExprError>
+    {
+        Expr::parse_with_labels(source, &HashMap::new())
+    }
+
+    pub fn parse_with_labels(source: &str, labels: &HashMap<String, u16>) -> Result<Expr, ExprError>
+    {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0, depth: 0, labels };
+        let root = parser.parse_binary(0)?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(ExprError(format!("Unexpected token {:?}", token)));
+        }
+
+        Ok(Expr { root })
+    }
+
+    pub fn eval(&self, ctx: &dyn ExprContext) -> Result<i64, ExprError>
+    {
+        eval(&self.root, ctx)
+    }
+
+    pub fn is_true(&self, ctx: &dyn ExprContext) -> Result<bool, ExprError>
+    {
+        Ok(self.eval(ctx)? != 0)
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ExprError>
+{
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap();
+        if c.is_ascii_digit() || c == '$' {
+            let (digits, radix) = if let Some(hex) = rest.strip_prefix("0x").or_else(|| rest.strip_prefix("0X")) {
+                (hex, 16)
+            }
+            else if let Some(hex) = rest.strip_prefix('$') {
+                (hex, 16)
+            }
+            else {
+                (rest, 10)
+            };
+
+            let len = digits.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(digits.len());
+            let val = i64::from_str_radix(&digits[..len], radix)
+                .map_err(|_| ExprError(format!("Invalid number '{}'", &digits[..len])))?;
+            tokens.push(Token::Number(val));
+            rest = &digits[len..];
+        }
+        else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            rest = &rest[len..];
+        }
+        else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        }
+        else {
+            return Err(ExprError(format!("Unexpected character '{}'", c)));
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+// Parsing and evaluation recurse once per nesting level, user input deeper
+// than this is rejected rather than allowed to overflow the stack
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a>
+{
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+    labels: &'a HashMap<String, u16>
+}
+
+impl Parser<'_>
+{
+    fn parse_binary(&mut self, level: usize) -> Result<Node, ExprError>
+    {
+        if level == PRECEDENCE.len() {
+            return self.parse_unary();
+        }
+
+        let mut lhs = self.parse_binary(level + 1)?;
+        while let Some(Token::Op(op)) = self.tokens.get(self.pos) {
+            let Some((_, bin_op)) = PRECEDENCE[level].iter().find(|(name, _)| name == op) else {
+                break;
+            };
+
+            self.pos += 1;
+            let rhs = self.parse_binary(level + 1)?;
+            lhs = Node::Binary(*bin_op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, ExprError>
+    {
+        let op = match self.tokens.get(self.pos) {
+            Some(Token::Op("-")) => UnaryOp::Neg,
+            Some(Token::Op("!")) => UnaryOp::Not,
+            Some(Token::Op("~")) => UnaryOp::BitNot,
+            _ => return self.parse_primary()
+        };
+
+        self.pos += 1;
+        let arg = self.nested(Parser::parse_unary)?;
+        Ok(Node::Unary(op, Box::new(arg)))
+    }
+
+    fn parse_primary(&mut self) -> Result<Node, ExprError>
+    {
+        let token = self.tokens.get(self.pos).cloned()
+            .ok_or_else(|| ExprError("Unexpected end of expression".into()))?;
+        self.pos += 1;
+
+        match token {
+            Token::Number(val) => Ok(Node::Number(val)),
+            Token::Ident(name) => self.resolve(&name),
+            Token::Op("(") => {
+                let node = self.nested(|parser| parser.parse_binary(0))?;
+                self.expect(")")?;
+                Ok(node)
+            },
+            Token::Op("[") => {
+                let node = self.nested(|parser| parser.parse_binary(0))?;
+                self.expect("]")?;
+                Ok(Node::Memory(Box::new(node)))
+            },
+            Token::Op(op) => Err(ExprError(format!("Unexpected operator '{}'", op)))
+        }
+    }
+
+    fn nested(&mut self, parse: impl FnOnce(&mut Self) -> Result<Node, ExprError>) -> Result<Node, ExprError>
+    {
+        if self.depth == MAX_DEPTH {
+            return Err(ExprError(format!("Expression is nested more than {} levels deep", MAX_DEPTH)));
+        }
+
+        self.depth += 1;
+        let node = parse(self);
+        self.depth -= 1;
+        node
+    }
+
+    fn resolve(&self, name: &str) -> Result<Node, ExprError>
+    {
+        let reg = match name.to_ascii_uppercase().as_str() {
+            "A" => Some(Register::A),
+            "X" => Some(Register::X),
+            "Y" => Some(Register::Y),
+            "SP" => Some(Register::SP),
+            "PC" => Some(Register::PC),
+            "PS" | "P" => Some(Register::PS),
+            _ => None
+        };
+
+        if let Some(reg) = reg {
+            return Ok(Node::Register(reg));
+        }
+
+        self.labels.get(name)
+            .map(|addr| Node::Number(*addr as i64))
+            .ok_or_else(|| ExprError(format!("Unknown symbol '{}'", name)))
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), ExprError>
+    {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(found)) if *found == op => {
+                self.pos += 1;
+                Ok(())
+            },
+            _ => Err(ExprError(format!("Expected '{}'", op)))
+        }
+    }
+}
+
+fn eval(node: &Node, ctx: &dyn ExprContext) -> Result<i64, ExprError>
+{
+    let val = match node {
+        Node::Number(val) => *val,
+        Node::Register(reg) => ctx.get_register(*reg) as i64,
+        Node::Memory(addr) => ctx.peek8(eval(addr, ctx)? as u16) as i64,
+        Node::Unary(op, arg) => {
+            let arg = eval(arg, ctx)?;
+            match op {
+                UnaryOp::Neg => arg.wrapping_neg(),
+                UnaryOp::Not => (arg == 0) as i64,
+                UnaryOp::BitNot => !arg
+            }
+        },
+        Node::Binary(BinaryOp::Or, lhs, rhs) => (eval(lhs, ctx)? != 0 || eval(rhs, ctx)? != 0) as i64,
+        Node::Binary(BinaryOp::And, lhs, rhs) => (eval(lhs, ctx)? != 0 && eval(rhs, ctx)? != 0) as i64,
+        Node::Binary(op, lhs, rhs) => {
+            let lhs = eval(lhs, ctx)?;
+            let rhs = eval(rhs, ctx)?;
+            match op {
+                BinaryOp::Eq => (lhs == rhs) as i64,
+                BinaryOp::Ne => (lhs != rhs) as i64,
+                BinaryOp::Lt => (lhs < rhs) as i64,
+                BinaryOp::Le => (lhs <= rhs) as i64,
+                BinaryOp::Gt => (lhs > rhs) as i64,
+                BinaryOp::Ge => (lhs >= rhs) as i64,
+                BinaryOp::BitOr => lhs | rhs,
+                BinaryOp::BitXor => lhs ^ rhs,
+                BinaryOp::BitAnd => lhs & rhs,
+                BinaryOp::Add => lhs.wrapping_add(rhs),
+                BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                BinaryOp::Mul => lhs.wrapping_mul(rhs),
+                BinaryOp::Div | BinaryOp::Rem if rhs == 0 => return Err(ExprError("Division by zero".into())),
+                BinaryOp::Div => lhs.wrapping_div(rhs),
+                BinaryOp::Rem => lhs.wrapping_rem(rhs),
+                BinaryOp::Or | BinaryOp::And => unreachable!()
+            }
+        }
+    };
+
+    Ok(val)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::collections::HashMap;
+    use super::*;
+
+    struct TestContext
+    {
+        memory: Vec<u8>,
+        x: u16
+    }
+
+    impl ExprContext for TestContext
+    {
+        fn get_register(&self, reg: Register) -> u16
+        {
+            match reg {
+                Register::X => self.x,
+                Register::PC => 0x8000,
+                _ => 0
+            }
+        }
+
+        fn peek8(&self, addr: u16) -> u8
+        {
+            self.memory[addr as usize]
+        }
+    }
+
+    fn context() -> TestContext
+    {
+        let mut memory = vec![0; 0x10000];
+        memory[0x00FE] = 3;
+        memory[0x0010] = 0xFE;
+        TestContext { memory, x: 4 }
+    }
+
+    fn eval(source: &str) -> i64
+    {
+        Expr::parse(source).unwrap().eval(&context()).unwrap()
+    }
+
+    #[test]
+    fn numbers()
+    {
+        assert_eq!(42, eval("42"));
+        assert_eq!(0xFE, eval("0xFE"));
+        assert_eq!(0xFE, eval("$fe"));
+    }
+
+    #[test]
+    fn precedence()
+    {
+        assert_eq!(14, eval("2 + 3 * 4"));
+        assert_eq!(20, eval("(2 + 3) * 4"));
+        assert_eq!(1, eval("1 + 1 == 2 && 3 > 2"));
+        assert_eq!(-5, eval("-5"));
+        assert_eq!(0, eval("!7"));
+    }
+
+    #[test]
+    fn memory_and_registers()
+    {
+        assert_eq!(1, eval("[0x00FE] + X > 5"));
+        assert_eq!(0, eval("[0x00FE] + x > 7"));
+        assert_eq!(3, eval("[[0x10]]"));
+        assert_eq!(0x8000, eval("PC"));
+    }
+
+    #[test]
+    fn labels()
+    {
+        let mut labels = HashMap::new();
+        labels.insert("player_hp".to_string(), 0x00FE);
+
+        let expr = Expr::parse_with_labels("[player_hp] == 3", &labels).unwrap();
+        assert!(expr.is_true(&context()).unwrap());
+    }
+
+    #[test]
+    fn parse_errors()
+    {
+        assert!(Expr::parse("unknown_label").is_err());
+        assert!(Expr::parse("[0x10").is_err());
+        assert!(Expr::parse("1 +").is_err());
+        assert!(Expr::parse("1 2").is_err());
+        assert!(Expr::parse("1 @ 2").is_err());
+    }
+
+    #[test]
+    fn nesting_limit()
+    {
+        let nested = |open: &str, close: &str, depth: usize| format!("{}1{}", open.repeat(depth), close.repeat(depth));
+
+        assert_eq!(1, Expr::parse(&nested("(", ")", 64)).unwrap().eval(&context()).unwrap());
+        assert!(Expr::parse(&nested("(", ")", 65)).is_err());
+        assert!(Expr::parse(&nested("[", "]", 10000)).is_err());
+        assert!(Expr::parse(&nested("-", "", 10000)).is_err());
+        assert!(Expr::parse(&nested("!(", ")", 5000)).is_err());
+    }
+
+    #[test]
+    fn division_by_zero()
+    {
+        assert!(Expr::parse("1 / 0").unwrap().eval(&context()).is_err());
+    }
+}
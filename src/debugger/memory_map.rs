@@ -0,0 +1,99 @@
+const RAM_SIZE: u16 = 0x800;
+const PPU_REGISTERS: u16 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region
+{
+    Ram,
+    PpuRegisters,
+    ApuIo,
+    TestMode,
+    Cartridge
+}
+
+impl Region
+{
+    pub fn of(addr: u16) -> Region
+    {
+        match addr {
+            0x0000..=0x1FFF => Region::Ram,
+            0x2000..=0x3FFF => Region::PpuRegisters,
+            0x4000..=0x4017 => Region::ApuIo,
+            0x4018..=0x401F => Region::TestMode,
+            _ => Region::Cartridge
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str
+    {
+        match self {
+            Region::Ram => "RAM",
+            Region::PpuRegisters => "PPU registers",
+            Region::ApuIo => "APU and I/O registers",
+            Region::TestMode => "CPU test mode",
+            Region::Cartridge => "Cartridge"
+        }
+    }
+}
+
+// Maps a mirrored address to the lowest address of the same location
+pub fn canonicalize(addr: u16) -> u16
+{
+    match Region::of(addr) {
+        Region::Ram => addr % RAM_SIZE,
+        Region::PpuRegisters => 0x2000 + (addr - 0x2000) % PPU_REGISTERS,
+        _ => addr
+    }
+}
+
+// Every address that accesses the same location as addr, in ascending order
+pub fn mirrors(addr: u16) -> Vec<u16>
+{
+    let canonical = canonicalize(addr);
+    match Region::of(addr) {
+        Region::Ram => (canonical..0x2000).step_by(RAM_SIZE as usize).collect(),
+        Region::PpuRegisters => (canonical..0x4000).step_by(PPU_REGISTERS as usize).collect(),
+        _ => vec![addr]
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn regions()
+    {
+        assert_eq!(Region::Ram, Region::of(0x1FFF));
+        assert_eq!(Region::PpuRegisters, Region::of(0x2000));
+        assert_eq!(Region::ApuIo, Region::of(0x4017));
+        assert_eq!(Region::TestMode, Region::of(0x4018));
+        assert_eq!(Region::Cartridge, Region::of(0x4020));
+    }
+
+    #[test]
+    fn canonicalize_mirrors()
+    {
+        assert_eq!(0x0123, canonicalize(0x1923));
+        assert_eq!(0x2002, canonicalize(0x3FFA));
+        assert_eq!(0x4016, canonicalize(0x4016));
+        assert_eq!(0x8000, canonicalize(0x8000));
+    }
+
+    #[test]
+    fn ram_mirrors()
+    {
+        assert_eq!(vec![0x0010, 0x0810, 0x1010, 0x1810], mirrors(0x1010));
+    }
+
+    #[test]
+    fn ppu_mirrors()
+    {
+        let mirrors = mirrors(0x2002);
+
+        assert_eq!(1024, mirrors.len());
+        assert_eq!(0x2002, mirrors[0]);
+        assert_eq!(0x3FFA, mirrors[1023]);
+    }
+}
@@ -0,0 +1,7 @@
+#[cfg(feature = "debugger")]
+pub mod expr;
+#[cfg(feature = "debugger")]
+pub mod breakpoint;
+#[cfg(feature = "debugger")]
+pub mod dbginfo;
+pub mod memory_map;
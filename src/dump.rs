@@ -0,0 +1,158 @@
+use std::error::Error;
+use std::io::{BufRead, Write, self};
+
+use crate::bus::RegisterWrite;
+
+const HEX_RECORD_SIZE: usize = 16;
+
+mod record
+{
+    pub const DATA: u8 = 0x00;
+    pub const END_OF_FILE: u8 = 0x01;
+    pub const EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+}
+
+pub fn write_bin(writer: &mut dyn Write, data: &[u8]) -> io::Result<()>
+{
+    writer.write_all(data)
+}
+
+// Writes data as Intel HEX records starting at the given address
+pub fn write_hex(writer: &mut dyn Write, data: &[u8], base_addr: u32) -> io::Result<()>
+{
+    // Intel HEX addresses are 32 bits wide, the last byte has to fit as well
+    let last_addr = u32::try_from(data.len()).ok().and_then(|len| base_addr.checked_add(len.saturating_sub(1)));
+    if last_addr.is_none() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Data doesn't fit in the 32-bit Intel HEX address space"));
+    }
+
+    let mut upper_addr = None;
+    for (i, chunk) in data.chunks(HEX_RECORD_SIZE).enumerate() {
+        let addr = base_addr + (i * HEX_RECORD_SIZE) as u32;
+        let upper = (addr >> 16) as u16;
+        if upper_addr != Some(upper) && (upper_addr.is_some() || upper != 0) {
+            write_hex_record(writer, 0, record::EXTENDED_LINEAR_ADDRESS, &upper.to_be_bytes())?;
+        }
+        upper_addr = Some(upper);
+
+        write_hex_record(writer, addr as u16, record::DATA, chunk)?;
+    }
+
+    write_hex_record(writer, 0, record::END_OF_FILE, &[])
+}
+
+fn write_hex_record(writer: &mut dyn Write, addr: u16, record_type: u8, data: &[u8]) -> io::Result<()>
+{
+    let mut checksum = (data.len() as u8)
+        .wrapping_add((addr >> 8) as u8)
+        .wrapping_add(addr as u8)
+        .wrapping_add(record_type);
+
+    write!(writer, ":{:02X}{:04X}{:02X}", data.len(), addr, record_type)?;
+    for byte in data {
+        write!(writer, "{:02X}", byte)?;
+        checksum = checksum.wrapping_add(*byte);
+    }
+    writeln!(writer, "{:02X}", checksum.wrapping_neg())
+}
+
+// Writes register writes one per line as "<cycle> <addr> <val>", with the
+// address and value in hex
+pub fn write_register_log(writer: &mut dyn Write, log: &[RegisterWrite]) -> io::Result<()>
+{
+    for write in log {
+        writeln!(writer, "{} {:04X} {:02X}", write.cycle, write.addr, write.val)?;
+    }
+    Ok(())
+}
+
+pub fn read_register_log(reader: impl BufRead) -> Result<Vec<RegisterWrite>, Box<dyn Error>>
+{
+    let mut log = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let write = match fields[..] {
+            [] => continue,
+            [cycle, addr, val] => RegisterWrite {
+                cycle: cycle.parse()?,
+                addr: u16::from_str_radix(addr, 16)?,
+                val: u8::from_str_radix(val, 16)?
+            },
+            _ => return Err(format!("Malformed register log entry on line {}", i + 1).into())
+        };
+        log.push(write);
+    }
+    Ok(log)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn bin()
+    {
+        let mut out = Vec::new();
+        write_bin(&mut out, &[0x01, 0x02, 0x03]).unwrap();
+
+        assert_eq!(vec![0x01, 0x02, 0x03], out);
+    }
+
+    #[test]
+    fn hex()
+    {
+        let mut out = Vec::new();
+        let data: Vec<u8> = (0..20).collect();
+        write_hex(&mut out, &data, 0x0100).unwrap();
+
+        assert_eq!(
+            ":10010000000102030405060708090A0B0C0D0E0F77\n\
+             :0401100010111213A5\n\
+             :00000001FF\n",
+            String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn hex_address_overflow()
+    {
+        let mut out = Vec::new();
+
+        assert_eq!(io::ErrorKind::InvalidInput, write_hex(&mut out, &[0; 2], u32::MAX).unwrap_err().kind());
+        assert_eq!(io::ErrorKind::InvalidInput, write_hex(&mut out, &[0; 32], u32::MAX - 16).unwrap_err().kind());
+        assert!(out.is_empty());
+
+        write_hex(&mut out, &[0; 17], u32::MAX - 16).unwrap();
+        write_hex(&mut out, &[], u32::MAX).unwrap();
+    }
+
+    #[test]
+    fn hex_extended_address()
+    {
+        let mut out = Vec::new();
+        write_hex(&mut out, &[0xAA; 32], 0xFFF0).unwrap();
+
+        assert_eq!(
+            ":10FFF000AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA61\n\
+             :020000040001F9\n\
+             :10000000AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA50\n\
+             :00000001FF\n",
+            String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn register_log()
+    {
+        let log = vec![
+            RegisterWrite { cycle: 7, addr: 0x4000, val: 0x3F },
+            RegisterWrite { cycle: 29781, addr: 0x4015, val: 0x0F }
+        ];
+        let mut out = Vec::new();
+        write_register_log(&mut out, &log).unwrap();
+
+        assert_eq!("7 4000 3F\n29781 4015 0F\n", String::from_utf8(out.clone()).unwrap());
+        assert_eq!(log, read_register_log(&out[..]).unwrap());
+        assert!(read_register_log(&b"7 4000\n"[..]).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use crate::bus::Bus;
+use crate::cpu::{CPU, FaultMode};
+use crate::rom::{self, INESRom};
+
+// Largest program that fits in CPU RAM, the rest of the input is ignored
+const MAX_PROGRAM_SIZE: usize = 0x800;
+
+// Runs arbitrary bytes as a program from address 0 for at most max_cycles.
+// Faults are expected and ignored, only a panic counts as a failure.
+pub fn run_program(data: &[u8], max_cycles: usize)
+{
+    let mut bus = Bus::new();
+    bus.write_buffer(0, &data[..data.len().min(MAX_PROGRAM_SIZE)]);
+
+    let mut cpu = CPU::new(Box::new(bus));
+    cpu.set_fault_mode(FaultMode::Lenient);
+    cpu.set_journal_capacity(16);
+    cpu.run_cycles(max_cycles as u64);
+
+    // Unwinding exercises the journal against whatever the program wrote
+    while cpu.step_back() {}
+}
+
+// Feeds arbitrary bytes to every ROM entry point. Malformed input must come
+// back as an error or warnings, never as a panic.
+pub fn parse_rom(data: &[u8])
+{
+    let _ = INESRom::validate(data);
+    let _ = rom::scan(data, true);
+
+    if let Ok(rom) = INESRom::from_reader(data) {
+        rom.prg_crc32();
+        rom.chr_crc32();
+        rom.sha1();
+        rom.get_prg_bank(0);
+        rom.get_chr_bank(0);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{parse_rom, run_program};
+
+    #[test]
+    fn every_opcode()
+    {
+        for op_code in 0..=0xFF {
+            run_program(&[op_code, 0x80, 0x80, op_code, 0xFF, 0xFF], 32);
+        }
+        run_program(&[], 32);
+    }
+
+    #[test]
+    fn malformed_roms()
+    {
+        parse_rom(&[]);
+        parse_rom(b"NES\x1A");
+        parse_rom(&[0xFF; 16]);
+        parse_rom(b"NES\x1A\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF");
+        parse_rom(b"NES\x1A\x01\x00\x06\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x02");
+    }
+}
@@ -0,0 +1,119 @@
+// Devices that can pull the IRQ line low, each gets its own bit so one source
+// acknowledging doesn't release the line for the others
+pub mod irq_source
+{
+    pub const APU_FRAME_COUNTER: u32 = 0b0001;
+    pub const APU_DMC: u32 = 0b0010;
+    pub const MAPPER: u32 = 0b0100;
+    pub const EXTERNAL: u32 = 0b1000;
+}
+
+// Level-triggered IRQ: asserted for as long as any source holds it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IrqLine
+{
+    sources: u32
+}
+
+impl IrqLine
+{
+    pub fn new() -> IrqLine
+    {
+        IrqLine { sources: 0 }
+    }
+
+    pub fn set(&mut self, source: u32, asserted: bool)
+    {
+        if asserted {
+            self.sources |= source;
+        }
+        else {
+            self.sources &= !source;
+        }
+    }
+
+    pub fn is_asserted(&self) -> bool
+    {
+        self.sources != 0
+    }
+
+    pub fn is_asserted_by(&self, source: u32) -> bool
+    {
+        self.sources & source != 0
+    }
+}
+
+// Edge-triggered NMI: a request is latched when the line becomes asserted and
+// stays pending until the CPU services it, even if the line is released
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NmiLine
+{
+    asserted: bool,
+    pending: bool
+}
+
+impl NmiLine
+{
+    pub fn new() -> NmiLine
+    {
+        NmiLine { asserted: false, pending: false }
+    }
+
+    pub fn set(&mut self, asserted: bool)
+    {
+        if asserted && !self.asserted {
+            self.pending = true;
+        }
+        self.asserted = asserted;
+    }
+
+    pub fn is_pending(&self) -> bool
+    {
+        self.pending
+    }
+
+    // Acknowledges the pending request, returns whether there was one
+    pub fn take(&mut self) -> bool
+    {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{irq_source, IrqLine, NmiLine};
+
+    #[test]
+    fn irq_sources_ored()
+    {
+        let mut irq = IrqLine::new();
+        irq.set(irq_source::APU_FRAME_COUNTER, true);
+        irq.set(irq_source::MAPPER, true);
+        irq.set(irq_source::APU_FRAME_COUNTER, false);
+
+        assert!(irq.is_asserted());
+        assert!(irq.is_asserted_by(irq_source::MAPPER));
+        assert!(!irq.is_asserted_by(irq_source::APU_FRAME_COUNTER));
+
+        irq.set(irq_source::MAPPER, false);
+        assert!(!irq.is_asserted());
+    }
+
+    #[test]
+    fn nmi_on_edge_only()
+    {
+        let mut nmi = NmiLine::new();
+        nmi.set(true);
+        assert!(nmi.take());
+
+        nmi.set(true);
+        assert!(!nmi.take());
+
+        nmi.set(false);
+        nmi.set(true);
+        nmi.set(false);
+        assert!(nmi.is_pending());
+        assert!(nmi.take());
+    }
+}
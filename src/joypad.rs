@@ -0,0 +1,278 @@
+// Bits of the standard controller's shift register, in the order they're read
+pub mod button
+{
+    pub const A: u8 = 0b00000001;
+    pub const B: u8 = 0b00000010;
+    pub const SELECT: u8 = 0b00000100;
+    pub const START: u8 = 0b00001000;
+    pub const UP: u8 = 0b00010000;
+    pub const DOWN: u8 = 0b00100000;
+    pub const LEFT: u8 = 0b01000000;
+    pub const RIGHT: u8 = 0b10000000;
+}
+
+// Standard controller with its 4021 shift register
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Joypad
+{
+    buttons: u8,
+    shift: u8,
+    strobe: bool
+}
+
+impl Joypad
+{
+    pub fn new() -> Joypad
+    {
+        Joypad { buttons: 0, shift: 0, strobe: false }
+    }
+
+    // Buttons currently held, as a mask of button bits
+    pub fn set_buttons(&mut self, buttons: u8)
+    {
+        self.buttons = buttons;
+    }
+
+    pub fn get_buttons(&self) -> u8
+    {
+        self.buttons
+    }
+
+    // Bit 0 of $4016 drives the strobe line of both ports
+    pub fn write(&mut self, val: u8)
+    {
+        let strobe = val & 1 != 0;
+        if self.strobe && !strobe {
+            self.shift = self.buttons;
+        }
+        self.strobe = strobe;
+    }
+
+    pub fn peek(&self) -> u8
+    {
+        // While strobed the register keeps reloading, so only A is ever seen
+        if self.strobe { self.buttons & button::A } else { self.shift & 1 }
+    }
+
+    pub fn read(&mut self) -> u8
+    {
+        let val = self.peek();
+        if !self.strobe {
+            // Official controllers shift in 1s, so reads past the 8th return 1
+            self.shift = (self.shift >> 1) | 0x80;
+        }
+        val
+    }
+}
+
+const POWER_PAD_BUTTONS: u16 = 12;
+
+// Order in which the Power Pad buttons (numbered 1-12 as on side B) are
+// shifted out on D3 and D4
+const POWER_PAD_D3: [u16; 8] = [2, 1, 5, 9, 6, 10, 11, 7];
+const POWER_PAD_D4: [u16; 4] = [4, 3, 12, 8];
+
+// Bandai/Nintendo Power Pad mat. It has two shift registers that are read in
+// parallel on bits 3 and 4.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PowerPad
+{
+    buttons: u16,
+    shift_d3: u8,
+    shift_d4: u8,
+    strobe: bool
+}
+
+impl PowerPad
+{
+    pub fn new() -> PowerPad
+    {
+        PowerPad { buttons: 0, shift_d3: 0, shift_d4: 0, strobe: false }
+    }
+
+    // Returns false without changing anything if the button is not 1-12
+    pub fn set_pressed(&mut self, button: u16, pressed: bool) -> bool
+    {
+        let Some(mask) = Self::button_mask(button) else {
+            return false;
+        };
+
+        if pressed {
+            self.buttons |= mask;
+        }
+        else {
+            self.buttons &= !mask;
+        }
+        true
+    }
+
+    pub fn is_pressed(&self, button: u16) -> bool
+    {
+        Self::button_mask(button).is_some_and(|mask| self.buttons & mask != 0)
+    }
+
+    fn button_mask(button: u16) -> Option<u16>
+    {
+        (1..=POWER_PAD_BUTTONS).contains(&button).then(|| 1 << (button - 1))
+    }
+
+    pub fn write(&mut self, val: u8)
+    {
+        let strobe = val & 1 != 0;
+        if self.strobe && !strobe {
+            (self.shift_d3, self.shift_d4) = self.serialize();
+        }
+        self.strobe = strobe;
+    }
+
+    pub fn peek(&self) -> u8
+    {
+        let (d3, d4) = if self.strobe { self.serialize() } else { (self.shift_d3, self.shift_d4) };
+        ((d3 & 1) << 3) | ((d4 & 1) << 4)
+    }
+
+    pub fn read(&mut self) -> u8
+    {
+        let val = self.peek();
+        if !self.strobe {
+            self.shift_d3 = (self.shift_d3 >> 1) | 0x80;
+            self.shift_d4 = (self.shift_d4 >> 1) | 0x80;
+        }
+        val
+    }
+
+    fn serialize(&self) -> (u8, u8)
+    {
+        let bits = |order: &[u16]| order.iter()
+            .enumerate()
+            .filter(|(_, button)| self.is_pressed(**button))
+            .fold(0u8, |acc, (i, _)| acc | 1 << i);
+
+        // D4 only carries 4 buttons, the rest of its register reads as 1
+        (bits(&POWER_PAD_D3), bits(&POWER_PAD_D4) | 0xF0)
+    }
+}
+
+// Device plugged into one of the controller ports
+#[derive(Debug, Clone, Copy)]
+pub enum Peripheral
+{
+    None,
+    Joypad(Joypad),
+    PowerPad(PowerPad)
+}
+
+impl Peripheral
+{
+    pub fn write(&mut self, val: u8)
+    {
+        match self {
+            Peripheral::None => {},
+            Peripheral::Joypad(pad) => pad.write(val),
+            Peripheral::PowerPad(pad) => pad.write(val)
+        }
+    }
+
+    pub fn peek(&self) -> u8
+    {
+        match self {
+            Peripheral::None => 0,
+            Peripheral::Joypad(pad) => pad.peek(),
+            Peripheral::PowerPad(pad) => pad.peek()
+        }
+    }
+
+    pub fn read(&mut self) -> u8
+    {
+        match self {
+            Peripheral::None => 0,
+            Peripheral::Joypad(pad) => pad.read(),
+            Peripheral::PowerPad(pad) => pad.read()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{button, Joypad, PowerPad};
+
+    fn read_all(pad: &mut Joypad, n: usize) -> Vec<u8>
+    {
+        (0..n).map(|_| pad.read()).collect()
+    }
+
+    #[test]
+    fn read_buttons()
+    {
+        let mut pad = Joypad::new();
+        pad.set_buttons(button::A | button::START | button::RIGHT);
+        pad.write(1);
+        pad.write(0);
+
+        assert_eq!(vec![1, 0, 0, 1, 0, 0, 0, 1, 1, 1], read_all(&mut pad, 10));
+    }
+
+    #[test]
+    fn strobe_high_returns_a()
+    {
+        let mut pad = Joypad::new();
+        pad.set_buttons(button::B);
+        pad.write(1);
+        assert_eq!(vec![0, 0, 0], read_all(&mut pad, 3));
+
+        pad.set_buttons(button::A);
+        assert_eq!(vec![1, 1, 1], read_all(&mut pad, 3));
+    }
+
+    #[test]
+    fn latched_on_falling_edge()
+    {
+        let mut pad = Joypad::new();
+        pad.write(1);
+        pad.set_buttons(button::SELECT);
+        pad.write(0);
+        pad.set_buttons(0);
+
+        assert_eq!(vec![0, 0, 1, 0], read_all(&mut pad, 4));
+    }
+
+    #[test]
+    fn power_pad()
+    {
+        let mut pad = PowerPad::new();
+        pad.set_pressed(1, true);
+        pad.set_pressed(3, true);
+        pad.set_pressed(7, true);
+        pad.write(1);
+        pad.write(0);
+
+        let reads: Vec<u8> = (0..9).map(|_| pad.read()).collect();
+        assert_eq!(vec![0x00, 0x18, 0x00, 0x00, 0x10, 0x10, 0x10, 0x18, 0x18], reads);
+    }
+
+    #[test]
+    fn power_pad_strobed()
+    {
+        let mut pad = PowerPad::new();
+        pad.write(1);
+        pad.set_pressed(2, true);
+        pad.set_pressed(4, true);
+
+        assert_eq!(0x18, pad.read());
+        assert_eq!(0x18, pad.read());
+    }
+
+    #[test]
+    fn power_pad_invalid_buttons()
+    {
+        let mut pad = PowerPad::new();
+
+        assert!(pad.set_pressed(12, true));
+        for button in [0, 13, 17, u16::MAX] {
+            assert!(!pad.set_pressed(button, true));
+            assert!(!pad.is_pressed(button));
+        }
+        assert!(pad.is_pressed(12));
+    }
+}
@@ -1,6 +1,17 @@
 pub mod bus;
 pub mod rom;
+pub mod cartridge;
 pub mod cpu;
+#[cfg(feature = "ppu")]
+pub mod ppu;
+pub mod palette;
+pub mod screenshot;
+pub mod dump;
+pub mod debugger;
+pub mod interrupt;
+pub mod joypad;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
 
 #[cfg(test)]
 mod tests {
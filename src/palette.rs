@@ -0,0 +1,114 @@
+use std::error::Error;
+use std::io::Read;
+
+const COLORS: usize = 64;
+const EMPHASIS_COLORS: usize = COLORS * 8;
+
+pub type Color = [u8; 3];
+
+const DEFAULT_COLORS: [Color; COLORS] = [
+    [84, 84, 84],    [0, 30, 116],    [8, 16, 144],    [48, 0, 136],    [68, 0, 100],    [92, 0, 48],     [84, 4, 0],      [60, 24, 0],
+    [32, 42, 0],     [8, 58, 0],      [0, 64, 0],      [0, 60, 0],      [0, 50, 60],     [0, 0, 0],       [0, 0, 0],       [0, 0, 0],
+    [152, 150, 152], [8, 76, 196],    [48, 50, 236],   [92, 30, 228],   [136, 20, 176],  [160, 20, 100],  [152, 34, 32],   [120, 60, 0],
+    [84, 90, 0],     [40, 114, 0],    [8, 124, 0],     [0, 118, 40],    [0, 102, 120],   [0, 0, 0],       [0, 0, 0],       [0, 0, 0],
+    [236, 238, 236], [76, 154, 236],  [120, 124, 236], [176, 98, 236],  [228, 84, 236],  [236, 88, 180],  [236, 106, 100], [212, 136, 32],
+    [160, 170, 0],   [116, 196, 0],   [76, 208, 32],   [56, 204, 108],  [56, 180, 204],  [60, 60, 60],    [0, 0, 0],       [0, 0, 0],
+    [236, 238, 236], [168, 204, 236], [188, 188, 236], [212, 178, 236], [236, 174, 236], [236, 174, 212], [236, 180, 176], [228, 196, 144],
+    [204, 210, 120], [180, 222, 120], [168, 226, 144], [152, 226, 180], [160, 214, 228], [160, 162, 160], [0, 0, 0],       [0, 0, 0],
+];
+
+// Maps 6-bit PPU color indices to RGB. Palettes loaded from 512-entry files
+// also carry a precomputed variant for each of the 8 color emphasis settings.
+#[derive(Clone)]
+pub struct Palette
+{
+    colors: Vec<Color>
+}
+
+impl Default for Palette
+{
+    fn default() -> Self
+    {
+        Palette { colors: DEFAULT_COLORS.to_vec() }
+    }
+}
+
+impl Palette
+{
+    // Reads a .pal file: 64 or 512 RGB triplets
+    pub fn from_reader(mut reader: impl Read) -> Result<Palette, Box<dyn Error>>
+    {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+
+        if buf.len() != COLORS * 3 && buf.len() != EMPHASIS_COLORS * 3 {
+            return Err(format!("Palette must contain {} or {} colors, got {} bytes", COLORS, EMPHASIS_COLORS, buf.len()).into());
+        }
+
+        let colors = buf.chunks_exact(3)
+            .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+            .collect();
+
+        Ok(Palette { colors })
+    }
+
+    pub fn has_emphasis(&self) -> bool
+    {
+        self.colors.len() == EMPHASIS_COLORS
+    }
+
+    // Emphasis is the 3-bit BGR field from PPUMASK. It is ignored by 64-color palettes.
+    pub fn get_color(&self, index: u8, emphasis: u8) -> Color
+    {
+        let index = (index as usize) % COLORS;
+        if self.has_emphasis() {
+            return self.colors[(emphasis as usize & 0b111) * COLORS + index];
+        }
+
+        self.colors[index]
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::Palette;
+
+    #[test]
+    fn default_palette()
+    {
+        let palette = Palette::default();
+
+        assert!(!palette.has_emphasis());
+        assert_eq!([84, 84, 84], palette.get_color(0x00, 0));
+        assert_eq!([236, 238, 236], palette.get_color(0x20, 0b111));
+    }
+
+    #[test]
+    fn load_64_colors()
+    {
+        let data: Vec<u8> = (0..192).map(|i| i as u8).collect();
+        let palette = Palette::from_reader(&data[..]).unwrap();
+
+        assert!(!palette.has_emphasis());
+        assert_eq!([3, 4, 5], palette.get_color(0x01, 0));
+        assert_eq!([3, 4, 5], palette.get_color(0x41, 0b101));
+    }
+
+    #[test]
+    fn load_512_colors()
+    {
+        let data: Vec<u8> = (0..1536).map(|i| (i / 3) as u8).collect();
+        let palette = Palette::from_reader(&data[..]).unwrap();
+
+        assert!(palette.has_emphasis());
+        assert_eq!([1, 1, 1], palette.get_color(0x01, 0));
+        assert_eq!([129, 129, 129], palette.get_color(0x01, 0b010));
+    }
+
+    #[test]
+    fn invalid_size()
+    {
+        assert!(Palette::from_reader(&[0u8; 191][..]).is_err());
+    }
+}
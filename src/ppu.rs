@@ -0,0 +1,315 @@
+mod status
+{
+    pub const SPRITE_OVERFLOW: u8 = 0b00100000;
+    pub const SPRITE_0_HIT: u8 = 0b01000000;
+    pub const VBLANK: u8 = 0b10000000;
+    pub const MASK: u8 = SPRITE_OVERFLOW | SPRITE_0_HIT | VBLANK;
+}
+
+mod ctrl
+{
+    pub const NMI_ENABLE: u8 = 0b10000000;
+}
+
+mod register
+{
+    pub const PPUCTRL: usize = 0;
+    pub const PPUMASK: usize = 1;
+    pub const PPUSTATUS: usize = 2;
+    pub const OAMDATA: usize = 4;
+    pub const PPUSCROLL: usize = 5;
+    pub const PPUADDR: usize = 6;
+    pub const PPUDATA: usize = 7;
+}
+
+// Number of CPU cycles after power-up or reset during which the PPU ignores
+// writes to PPUCTRL, PPUMASK, PPUSCROLL and PPUADDR
+const WARM_UP_CYCLES: u32 = 29658;
+
+pub struct PPU
+{
+    registers: [u8; 8],
+    status: u8,
+    io_latch: u8,
+    write_toggle: bool,
+    warm_up_enabled: bool,
+    warm_up_cycles: u32
+}
+
+impl Default for PPU
+{
+    fn default() -> Self
+    {
+        PPU::new()
+    }
+}
+
+impl PPU
+{
+    pub fn new() -> PPU
+    {
+        PPU {
+            registers: [0; 8],
+            status: 0,
+            io_latch: 0,
+            write_toggle: false,
+            warm_up_enabled: true,
+            warm_up_cycles: WARM_UP_CYCLES
+        }
+    }
+
+    pub fn reset(&mut self)
+    {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(warm_up = self.warm_up_enabled, "PPU reset");
+
+        self.registers[register::PPUCTRL] = 0;
+        self.registers[register::PPUMASK] = 0;
+        self.registers[register::PPUSCROLL] = 0;
+        self.registers[register::PPUDATA] = 0;
+        self.write_toggle = false;
+
+        if self.warm_up_enabled {
+            self.warm_up_cycles = WARM_UP_CYCLES;
+        }
+    }
+
+    // Advances the PPU by one CPU cycle
+    pub fn tick(&mut self)
+    {
+        if self.warm_up_cycles > 0 {
+            self.warm_up_cycles -= 1;
+
+            #[cfg(feature = "tracing")]
+            if self.warm_up_cycles == 0 {
+                tracing::debug!("PPU warm-up finished");
+            }
+        }
+    }
+
+    pub fn set_warm_up(&mut self, enabled: bool)
+    {
+        self.warm_up_enabled = enabled;
+        if !enabled {
+            self.warm_up_cycles = 0;
+        }
+    }
+
+    pub fn is_warming_up(&self) -> bool
+    {
+        self.warm_up_cycles > 0
+    }
+
+    // Returns what reading the register would, without any of the side effects
+    pub fn peek_register(&self, index: usize) -> u8
+    {
+        match index {
+            // Only the top 3 bits are driven, the rest is whatever was left
+            // on the PPU data bus
+            register::PPUSTATUS => self.status | (self.io_latch & !status::MASK),
+            register::OAMDATA | register::PPUDATA => self.registers[index],
+            // Write-only registers
+            _ => self.io_latch
+        }
+    }
+
+    pub fn read_register(&mut self, index: usize) -> u8
+    {
+        let val = self.peek_register(index);
+        if index == register::PPUSTATUS {
+            // Acknowledge VBlank and reset the PPUSCROLL/PPUADDR write toggle
+            self.status &= !status::VBLANK;
+            self.write_toggle = false;
+        }
+
+        self.io_latch = val;
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(register = index, val, "PPU register read");
+
+        val
+    }
+
+    pub fn write_register(&mut self, index: usize, val: u8)
+    {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(register = index, val, "PPU register write");
+
+        self.io_latch = val;
+
+        match index {
+            register::PPUSTATUS => {},
+            register::PPUCTRL | register::PPUMASK |
+            register::PPUSCROLL | register::PPUADDR if self.is_warming_up() => {},
+            register::PPUSCROLL | register::PPUADDR => {
+                self.registers[index] = val;
+                self.write_toggle = !self.write_toggle;
+            },
+            _ => self.registers[index] = val
+        }
+    }
+
+    // The NMI output is low while VBlank is set and NMIs are enabled in PPUCTRL
+    pub fn is_nmi_asserted(&self) -> bool
+    {
+        self.status & status::VBLANK != 0 && self.is_nmi_enabled()
+    }
+
+    pub fn is_nmi_enabled(&self) -> bool
+    {
+        self.registers[register::PPUCTRL] & ctrl::NMI_ENABLE != 0
+    }
+
+    pub fn get_write_toggle(&self) -> bool
+    {
+        self.write_toggle
+    }
+
+    pub fn set_vblank(&mut self, is_set: bool)
+    {
+        self.set_status(status::VBLANK, is_set);
+    }
+
+    pub fn set_sprite_0_hit(&mut self, is_set: bool)
+    {
+        self.set_status(status::SPRITE_0_HIT, is_set);
+    }
+
+    pub fn set_sprite_overflow(&mut self, is_set: bool)
+    {
+        self.set_status(status::SPRITE_OVERFLOW, is_set);
+    }
+
+    fn set_status(&mut self, flag: u8, is_set: bool)
+    {
+        if is_set {
+            self.status |= flag;
+        }
+        else {
+            self.status &= !flag;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::PPU;
+
+    #[test]
+    fn status_low_bits_from_io_latch()
+    {
+        let mut ppu = PPU::new();
+        ppu.set_vblank(true);
+        ppu.write_register(0, 0b01011010);
+
+        assert_eq!(0b10011010, ppu.read_register(2));
+    }
+
+    #[test]
+    fn status_read_clears_vblank()
+    {
+        let mut ppu = PPU::new();
+        ppu.set_vblank(true);
+
+        assert_eq!(0x80, ppu.read_register(2) & 0x80);
+        assert_eq!(0, ppu.read_register(2) & 0x80);
+    }
+
+    #[test]
+    fn status_read_keeps_sprite_flags()
+    {
+        let mut ppu = PPU::new();
+        ppu.set_sprite_0_hit(true);
+        ppu.set_sprite_overflow(true);
+        ppu.read_register(2);
+
+        assert_eq!(0b01100000, ppu.read_register(2));
+    }
+
+    #[test]
+    fn status_read_resets_write_toggle()
+    {
+        let mut ppu = PPU::new();
+        ppu.set_warm_up(false);
+        ppu.write_register(6, 0x21);
+        assert!(ppu.get_write_toggle());
+
+        ppu.read_register(2);
+        assert!(!ppu.get_write_toggle());
+    }
+
+    #[test]
+    fn write_only_register_reads_io_latch()
+    {
+        let mut ppu = PPU::new();
+        ppu.write_register(5, 42);
+
+        assert_eq!(42, ppu.read_register(0));
+        assert_eq!(42, ppu.read_register(6));
+    }
+
+    #[test]
+    fn writes_ignored_during_warm_up()
+    {
+        let mut ppu = PPU::new();
+        ppu.write_register(6, 0x21);
+        ppu.write_register(4, 42);
+
+        assert!(ppu.is_warming_up());
+        assert!(!ppu.get_write_toggle());
+        assert_eq!(42, ppu.read_register(4));
+    }
+
+    #[test]
+    fn writes_accepted_after_warm_up()
+    {
+        let mut ppu = PPU::new();
+        for _ in 0..29658 {
+            ppu.tick();
+        }
+        ppu.write_register(6, 0x21);
+
+        assert!(!ppu.is_warming_up());
+        assert!(ppu.get_write_toggle());
+    }
+
+    #[test]
+    fn reset_restarts_warm_up()
+    {
+        let mut ppu = PPU::new();
+        for _ in 0..29658 {
+            ppu.tick();
+        }
+        ppu.reset();
+
+        assert!(ppu.is_warming_up());
+    }
+
+    #[test]
+    fn warm_up_disabled()
+    {
+        let mut ppu = PPU::new();
+        ppu.set_warm_up(false);
+        ppu.reset();
+        ppu.write_register(6, 0x21);
+
+        assert!(!ppu.is_warming_up());
+        assert!(ppu.get_write_toggle());
+    }
+
+    #[test]
+    fn nmi_output()
+    {
+        let mut ppu = PPU::new();
+        ppu.set_warm_up(false);
+        ppu.set_vblank(true);
+        assert!(!ppu.is_nmi_asserted());
+
+        ppu.write_register(0, 0x80);
+        assert!(ppu.is_nmi_asserted());
+
+        ppu.read_register(2);
+        assert!(!ppu.is_nmi_asserted());
+    }
+}
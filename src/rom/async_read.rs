@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::future::poll_fn;
+use std::pin::Pin;
+
+use futures_io::AsyncRead;
+
+use super::INESRom;
+
+const CHUNK_SIZE: usize = 0x4000;
+
+impl INESRom
+{
+    // Buffers the whole file without blocking, then parses it the same way
+    // as from_reader. ROMs are small enough that streaming the parse itself
+    // isn't worth it.
+    pub async fn from_async_reader(mut reader: impl AsyncRead + Unpin) -> Result<Self, Box<dyn Error>>
+    {
+        let mut data = Vec::new();
+        let mut chunk = [0; CHUNK_SIZE];
+        loop {
+            let len = poll_fn(|cx| Pin::new(&mut reader).poll_read(cx, &mut chunk)).await?;
+            if len == 0 {
+                break;
+            }
+            data.extend_from_slice(&chunk[..len]);
+        }
+
+        INESRom::from_reader(&data[..])
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    use super::INESRom;
+
+    fn block_on<F: Future>(future: F) -> F::Output
+    {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(val) = future.as_mut().poll(&mut cx) {
+                return val;
+            }
+        }
+    }
+
+    #[test]
+    fn read()
+    {
+        let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 2, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        bytes.extend(vec![0x42; 0x8000]);
+        bytes.extend(vec![0x24; 0x2000]);
+
+        let rom = block_on(INESRom::from_async_reader(&bytes[..])).unwrap();
+
+        assert_eq!(&bytes[16..0x8010], rom.prg_rom());
+        assert_eq!(&bytes[0x8010..], rom.chr_rom());
+        assert!(rom.get_warnings().is_empty());
+    }
+
+    #[test]
+    fn invalid_format()
+    {
+        let bytes = [0u8; 16];
+
+        assert!(block_on(INESRom::from_async_reader(&bytes[..])).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256]
+{
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+pub fn crc32(data: &[u8]) -> u32
+{
+    let mut hasher = Crc32::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+// Incremental CRC-32 for data that is read in chunks
+pub struct Crc32
+{
+    crc: u32
+}
+
+impl Default for Crc32
+{
+    fn default() -> Self
+    {
+        Crc32::new()
+    }
+}
+
+impl Crc32
+{
+    pub fn new() -> Crc32
+    {
+        Crc32 { crc: !0 }
+    }
+
+    pub fn update(&mut self, data: &[u8])
+    {
+        for byte in data {
+            self.crc = CRC32_TABLE[((self.crc ^ *byte as u32) & 0xFF) as usize] ^ (self.crc >> 8);
+        }
+    }
+
+    pub fn finish(self) -> u32
+    {
+        !self.crc
+    }
+}
+
+const SHA1_BLOCK_SIZE: usize = 64;
+
+pub struct Sha1
+{
+    state: [u32; 5],
+    block: [u8; SHA1_BLOCK_SIZE],
+    block_len: usize,
+    total_len: u64
+}
+
+impl Default for Sha1
+{
+    fn default() -> Self
+    {
+        Sha1::new()
+    }
+}
+
+impl Sha1
+{
+    pub fn new() -> Sha1
+    {
+        Sha1 {
+            state: [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0],
+            block: [0; SHA1_BLOCK_SIZE],
+            block_len: 0,
+            total_len: 0
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8])
+    {
+        self.total_len += data.len() as u64;
+        while !data.is_empty() {
+            let len = usize::min(SHA1_BLOCK_SIZE - self.block_len, data.len());
+            self.block[self.block_len..self.block_len + len].copy_from_slice(&data[..len]);
+            self.block_len += len;
+            data = &data[len..];
+
+            if self.block_len == SHA1_BLOCK_SIZE {
+                self.process_block();
+                self.block_len = 0;
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> [u8; 20]
+    {
+        let bit_len = self.total_len * 8;
+        self.update(&[0x80]);
+        while self.block_len != SHA1_BLOCK_SIZE - 8 {
+            self.update(&[0]);
+        }
+        self.update(&bit_len.to_be_bytes());
+
+        let mut digest = [0; 20];
+        for (i, word) in self.state.iter().enumerate() {
+            digest[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn process_block(&mut self)
+    {
+        let mut w = [0u32; 80];
+        for (i, word) in self.block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = self.state;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6)
+            };
+
+            let temp = a.rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (state, val) in self.state.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(val);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn sha1(data: &[u8]) -> String
+    {
+        let mut hasher = Sha1::new();
+        hasher.update(data);
+        hasher.finish().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn crc32_check_value()
+    {
+        assert_eq!(0xCBF43926, crc32(b"123456789"));
+        assert_eq!(0, crc32(b""));
+    }
+
+    #[test]
+    fn sha1_test_vectors()
+    {
+        assert_eq!("da39a3ee5e6b4b0d3255bfef95601890afd80709", sha1(b""));
+        assert_eq!("a9993e364706816aba3e25717850c26c9cd0d89d", sha1(b"abc"));
+        assert_eq!("84983e441c3bd26ebaae4aa1f95129e5e54670f1", sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"));
+    }
+
+    #[test]
+    fn sha1_split_updates()
+    {
+        let data = vec![0xA5; 1000];
+        let mut hasher = Sha1::new();
+        hasher.update(&data[..10]);
+        hasher.update(&data[10..700]);
+        hasher.update(&data[700..]);
+
+        assert_eq!(sha1(&data), hasher.finish().iter().map(|b| format!("{:02x}", b)).collect::<String>());
+    }
+}
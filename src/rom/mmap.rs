@@ -0,0 +1,86 @@
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use super::{validate, INESRom, Issue, Storage, PADDING};
+
+impl INESRom
+{
+    // Maps the file instead of copying it, so PRG and CHR banks borrow from
+    // the page cache. Truncated files still need padding and are copied.
+    pub fn from_mmap(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>>
+    {
+        let file = File::open(path)?;
+
+        // Safety: the mapping is read-only. Like any mapped file it must not be
+        // truncated or modified by another process while the ROM is alive.
+        let map = unsafe { Mmap::map(&file)? };
+
+        let header = INESRom::read_header(&mut &map[..])?;
+        let expected = validate::get_expected_size(&header);
+        let payload = &map[validate::HEADER_SIZE..];
+        let warnings: Vec<Issue> = validate::check_size(payload, expected - validate::HEADER_SIZE, validate::HEADER_SIZE)
+            .into_iter()
+            .collect();
+
+        if map.len() < expected {
+            let mut payload = payload.to_vec();
+            payload.resize(expected - validate::HEADER_SIZE, PADDING);
+            return Ok(INESRom::from_storage(header, Storage::Owned(payload), 0, warnings));
+        }
+
+        Ok(INESRom::from_storage(header, Storage::Mapped(map), validate::HEADER_SIZE, warnings))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::path::PathBuf;
+
+    use super::INESRom;
+    use crate::rom::Issue;
+
+    fn write_rom(name: &str, len: usize) -> PathBuf
+    {
+        let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        bytes.extend(vec![0x42; 0x4000]);
+        bytes.extend(vec![0x24; 0x2000]);
+        bytes.resize(len, 0);
+
+        let path = std::env::temp_dir().join(format!("nesemu-mmap-{}-{}.nes", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn read()
+    {
+        let path = write_rom("read", 0x6010);
+        let rom = INESRom::from_mmap(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(&[0x42; 0x4000][..], rom.prg_rom());
+        assert_eq!(&[0x24; 0x2000][..], rom.chr_rom());
+        assert!(rom.get_warnings().is_empty());
+    }
+
+    #[test]
+    fn truncated_padded()
+    {
+        let path = write_rom("truncated", 0x5010);
+        let rom = INESRom::from_mmap(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(0xFF, rom.chr_rom()[0x1000]);
+        assert_eq!(&[Issue::Truncated { expected: 0x6010, actual: 0x5010 }], rom.get_warnings());
+    }
+
+    #[test]
+    fn missing_file()
+    {
+        assert!(INESRom::from_mmap("/nonexistent/rom.nes").is_err());
+    }
+}
@@ -1,8 +1,28 @@
 use std::error::Error;
 use std::io::Read;
+use std::ops::Range;
+
+use crate::cartridge::CartridgeRam;
 
 use self::error::FormatError;
-use self::header::{INESHeader, Mirroring};
+use self::hash::Sha1;
+use self::header::INESHeader;
+
+#[cfg(feature = "async")]
+mod async_read;
+mod hash;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "overrides")]
+mod overrides;
+mod scan;
+mod validate;
+
+pub use self::header::Mirroring;
+#[cfg(feature = "overrides")]
+pub use self::overrides::{GameOverride, InputDevice, OverrideDatabase};
+pub use self::scan::{scan, scan_dir, RomInfo};
+pub use self::validate::{get_mapper_name, Issue, ValidationReport};
 
 mod error
 {
@@ -26,10 +46,15 @@ mod header
 {
     use std::{io::Read, error::Error};
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "overrides", derive(serde::Deserialize), serde(rename_all = "lowercase"))]
     pub enum Mirroring
     {
         Horizontal,
-        Vertical
+        Vertical,
+        // Four nametables, using VRAM on the cartridge
+        #[cfg_attr(feature = "overrides", serde(rename = "four-screen"))]
+        FourScreen
     }
 
     mod flag6
@@ -41,6 +66,9 @@ mod header
         pub const MAPPER_LOWER: u8 = 0b11110000; 
     }
     
+    const PRG_RAM_BANK_SIZE: usize = 0x2000;
+    const CHR_RAM_SIZE: usize = 0x2000;
+
     mod flag7
     {
         pub const VS_UNISYSTEM: u8 = 0b00000001;
@@ -49,6 +77,18 @@ mod header
         pub const MAPPER_UPPER: u8 = 0b11110000;
     }
 
+    mod flag10
+    {
+        pub const PRG_RAM_SHIFT: u8 = 0b00001111;
+        pub const PRG_NVRAM_SHIFT: u8 = 0b11110000;
+    }
+
+    mod flag11
+    {
+        pub const CHR_RAM_SHIFT: u8 = 0b00001111;
+        pub const CHR_NVRAM_SHIFT: u8 = 0b11110000;
+    }
+
     #[repr(C, packed)]
     pub struct INESHeader
     {
@@ -58,7 +98,13 @@ mod header
         pub flag6: u8,
         pub flag7: u8,
         pub prg_ram_banks: u8,
-        pub flag9: u8
+        pub flag9: u8,
+        pub flag10: u8,
+        pub flag11: u8,
+        pub flag12: u8,
+        pub flag13: u8,
+        pub flag14: u8,
+        pub flag15: u8
     }
 
     impl INESHeader 
@@ -68,7 +114,7 @@ mod header
             let mut buf = [0; std::mem::size_of::<INESHeader>()];
             reader.read_exact(&mut buf)?;
             unsafe {
-                Ok(std::mem::transmute(buf))
+                Ok(std::mem::transmute::<[u8; 16], INESHeader>(buf))
             }
         }
 
@@ -99,7 +145,10 @@ mod header
 
         pub fn get_mirroring(&self) -> Mirroring
         {
-            if self.flag6 & flag6::MIRRORING == 0 {
+            if self.get_ignore_mirroring() {
+                Mirroring::FourScreen
+            }
+            else if self.flag6 & flag6::MIRRORING == 0 {
                 Mirroring::Horizontal
             } 
             else {
@@ -116,6 +165,77 @@ mod header
         {
             self.flag7 & flag7::MAPPER_UPPER | (self.flag6 & flag6::MAPPER_LOWER) >> 4
         }
+
+        pub fn set_mapper(&mut self, mapper: u8)
+        {
+            self.flag6 = self.flag6 & !flag6::MAPPER_LOWER | (mapper << 4);
+            self.flag7 = self.flag7 & !flag7::MAPPER_UPPER | (mapper & flag7::MAPPER_UPPER);
+        }
+
+        pub fn set_mirroring(&mut self, mirroring: Mirroring)
+        {
+            self.flag6 &= !(flag6::MIRRORING | flag6::IGNORE_MIRRORING);
+            match mirroring {
+                Mirroring::Horizontal => {},
+                Mirroring::Vertical => self.flag6 |= flag6::MIRRORING,
+                Mirroring::FourScreen => self.flag6 |= flag6::IGNORE_MIRRORING
+            }
+        }
+
+        // NES 2.0 reuses the PRG-RAM size byte for the submapper number
+        pub fn get_submapper(&self) -> Option<u8>
+        {
+            if self.is_nes2_format() { Some(self.prg_ram_banks >> 4) } else { None }
+        }
+
+        pub fn get_prg_ram_size(&self) -> usize
+        {
+            if self.is_nes2_format() {
+                return shift_count_size(self.flag10 & flag10::PRG_RAM_SHIFT);
+            }
+
+            if self.has_persistent_memory() { 0 } else { self.get_ines_prg_ram_size() }
+        }
+
+        pub fn get_prg_nvram_size(&self) -> usize
+        {
+            if self.is_nes2_format() {
+                return shift_count_size((self.flag10 & flag10::PRG_NVRAM_SHIFT) >> 4);
+            }
+
+            if self.has_persistent_memory() { self.get_ines_prg_ram_size() } else { 0 }
+        }
+
+        pub fn get_chr_ram_size(&self) -> usize
+        {
+            if self.is_nes2_format() {
+                return shift_count_size(self.flag11 & flag11::CHR_RAM_SHIFT);
+            }
+
+            // iNES 1.0 boards without CHR-ROM carry 8K of CHR-RAM
+            if self.chr_rom_banks == 0 { CHR_RAM_SIZE } else { 0 }
+        }
+
+        pub fn get_chr_nvram_size(&self) -> usize
+        {
+            if self.is_nes2_format() {
+                return shift_count_size((self.flag11 & flag11::CHR_NVRAM_SHIFT) >> 4);
+            }
+
+            0
+        }
+
+        // iNES 1.0 stores the size in 8K units, with 0 meaning 8K for compatibility
+        fn get_ines_prg_ram_size(&self) -> usize
+        {
+            PRG_RAM_BANK_SIZE * usize::max(self.prg_ram_banks as usize, 1)
+        }
+    }
+
+    // NES 2.0 encodes RAM sizes as 64 << n bytes, with 0 meaning no RAM
+    fn shift_count_size(shift: u8) -> usize
+    {
+        if shift == 0 { 0 } else { 64 << shift }
     }
 
     #[cfg(test)]
@@ -123,20 +243,20 @@ mod header
     {
         use super::*;
 
-        fn header_with_flag6(flag6: u8) -> [u8; 10]
+        fn header_with_flag6(flag6: u8) -> [u8; 16]
         {
-            [0x4E, 0x45, 0x53, 0x1A, 0x0, 0x0, flag6, 0x0, 0x0, 0x0]
+            [0x4E, 0x45, 0x53, 0x1A, 0x0, 0x0, flag6, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0]
         }
 
-        fn header_with_flag7(flag7: u8) -> [u8; 10]
+        fn header_with_flag7(flag7: u8) -> [u8; 16]
         {
-            [0x4E, 0x45, 0x53, 0x1A, 0x0, 0x0, 0x0, flag7, 0x0, 0x0]
+            [0x4E, 0x45, 0x53, 0x1A, 0x0, 0x0, 0x0, flag7, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0]
         }
 
         #[test]
         fn read()
         {
-            let header_bytes = [0x4E, 0x45, 0x53, 0x1A, 0x1, 0x1, 0x0, 0x0, 0x1, 0x0];
+            let header_bytes = [0x4E, 0x45, 0x53, 0x1A, 0x1, 0x1, 0x0, 0x0, 0x1, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0];
             let header = INESHeader::from_reader(&mut &header_bytes[..]).unwrap();
 
             assert_eq!(header.format, [0x4E, 0x45, 0x53, 0x1A]);
@@ -159,6 +279,7 @@ mod header
                 0x0, // flag 7
                 0x0, // prg_ram_banks
                 0x0, // flag 9
+                0x0, 0x0, 0x0, 0x0, 0x0, 0x0 // flags 10-15
             ];
             
             let header = INESHeader::from_reader(&mut &header_bytes[..]).unwrap();
@@ -211,6 +332,15 @@ mod header
             assert!(matches!(header.get_mirroring(), Mirroring::Vertical))
         }
 
+        #[test]
+        fn read_mirroring_four_screen()
+        {
+            let header_bytes = header_with_flag6(0b00001001);
+            let header = INESHeader::from_reader(&mut &header_bytes[..]).unwrap();
+
+            assert!(matches!(header.get_mirroring(), Mirroring::FourScreen))
+        }
+
         #[test]
         fn has_persistent_memory()
         {
@@ -276,11 +406,96 @@ mod header
                 0b10010000, // flag 7
                 0x0, // prg_ram_banks
                 0x0, // flag 9
+                0x0, 0x0, 0x0, 0x0, 0x0, 0x0 // flags 10-15
             ];
             let header = INESHeader::from_reader(&mut &header_bytes[..]).unwrap();
 
             assert_eq!(header.get_mapper(), 0b10010110);
-        }     
+        }
+
+        #[test]
+        fn set_mapper_and_mirroring()
+        {
+            let header_bytes = header_with_flag6(0b00001111);
+            let mut header = INESHeader::from_reader(&mut &header_bytes[..]).unwrap();
+            header.set_mapper(0xA5);
+            header.set_mirroring(Mirroring::Horizontal);
+
+            assert_eq!(0xA5, header.get_mapper());
+            assert_eq!(0b01010110, header.flag6);
+            assert!(matches!(header.get_mirroring(), Mirroring::Horizontal));
+
+            header.set_mirroring(Mirroring::FourScreen);
+            assert_eq!(0b01011110, header.flag6);
+            assert!(matches!(header.get_mirroring(), Mirroring::FourScreen));
+        }
+
+        #[test]
+        fn ines_prg_ram_size()
+        {
+            let mut header_bytes = header_with_flag6(0);
+            header_bytes[8] = 2;
+            let header = INESHeader::from_reader(&mut &header_bytes[..]).unwrap();
+
+            assert_eq!(header.get_prg_ram_size(), 0x4000);
+            assert_eq!(header.get_prg_nvram_size(), 0);
+        }
+
+        #[test]
+        fn ines_prg_ram_battery_backed()
+        {
+            let header_bytes = header_with_flag6(0b00000010);
+            let header = INESHeader::from_reader(&mut &header_bytes[..]).unwrap();
+
+            assert_eq!(header.get_prg_ram_size(), 0);
+            assert_eq!(header.get_prg_nvram_size(), 0x2000);
+        }
+
+        #[test]
+        fn nes2_prg_ram_size()
+        {
+            let mut header_bytes = header_with_flag7(0b00001000);
+            header_bytes[10] = 0x07;
+            let header = INESHeader::from_reader(&mut &header_bytes[..]).unwrap();
+
+            assert_eq!(header.get_prg_ram_size(), 0x2000);
+            assert_eq!(header.get_prg_nvram_size(), 0);
+        }
+
+        #[test]
+        fn nes2_prg_nvram_size()
+        {
+            let mut header_bytes = header_with_flag7(0b00001000);
+            header_bytes[10] = 0x90;
+            let header = INESHeader::from_reader(&mut &header_bytes[..]).unwrap();
+
+            assert_eq!(header.get_prg_ram_size(), 0);
+            assert_eq!(header.get_prg_nvram_size(), 0x8000);
+        }
+
+        #[test]
+        fn ines_chr_ram_size()
+        {
+            let mut header_bytes = header_with_flag6(0);
+            let header = INESHeader::from_reader(&mut &header_bytes[..]).unwrap();
+            assert_eq!(header.get_chr_ram_size(), 0x2000);
+
+            header_bytes[5] = 1;
+            let header = INESHeader::from_reader(&mut &header_bytes[..]).unwrap();
+            assert_eq!(header.get_chr_ram_size(), 0);
+            assert_eq!(header.get_chr_nvram_size(), 0);
+        }
+
+        #[test]
+        fn nes2_chr_ram_sizes()
+        {
+            let mut header_bytes = header_with_flag7(0b00001000);
+            header_bytes[11] = 0x57;
+            let header = INESHeader::from_reader(&mut &header_bytes[..]).unwrap();
+
+            assert_eq!(header.get_chr_ram_size(), 0x2000);
+            assert_eq!(header.get_chr_nvram_size(), 0x800);
+        }
     }
 }
 
@@ -289,56 +504,115 @@ const PRG_ROM_BANK_SIZE: usize = 0x4000;
 const CHR_ROM_BANK_SIZE: usize = 0x2000;
 const PLAY_CHOICE_10_SIZE: usize = 0x2000;
 
+// Value of missing bytes in truncated dumps, as read from erased EPROM
+const PADDING: u8 = 0xFF;
+
+// Backing memory for the PRG and CHR data
+enum Storage
+{
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap)
+}
+
+impl std::ops::Deref for Storage
+{
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8]
+    {
+        match self {
+            Storage::Owned(data) => data,
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(map) => map
+        }
+    }
+}
+
 pub struct INESRom
 {
     header: INESHeader,
     trainer: Option<Vec<u8>>,
     play_chouice_10: Option<Vec<u8>>,
-    prg_banks: Vec<Vec<u8>>,
-    chr_banks: Vec<Vec<u8>>,
+    data: Storage,
+    prg_rom: Range<usize>,
+    chr_rom: Range<usize>,
+    warnings: Vec<Issue>
 }
 
 impl INESRom
 {
     pub fn from_reader(mut reader: impl Read) -> Result<Self, Box<dyn Error>>
     {
-        let header = INESHeader::from_reader(&mut reader)?;
-        if header.format != [0x4E, 0x45, 0x53, 0x1A]
-        {
-            return Err(Box::new(FormatError("Invalid format name".into())));
-        }
+        let header = INESRom::read_header(&mut reader)?;
 
-        let mut trainer = None;
-        if header.has_trainer()
-        {
-            trainer = Some(INESRom::read_bank(&mut reader, TRAINER_SIZE)?);
-        }
+        // Bad dumps and some homebrew don't match the sizes in the header, so
+        // the payload is padded or cut to what the header describes. Anything
+        // past that is only counted, not buffered.
+        let expected = validate::get_expected_size(&header) - validate::HEADER_SIZE;
+        let mut payload = Vec::new();
+        reader.by_ref().take(expected as u64).read_to_end(&mut payload)?;
 
-        let mut prg_banks = Vec::<Vec::<u8>>::new();
-        for _ in 0..header.prg_rom_banks
-        {
-            prg_banks.push(INESRom::read_bank(&mut reader, PRG_ROM_BANK_SIZE)?);
+        let issue = if payload.len() < expected {
+            validate::check_size(&payload, expected, validate::HEADER_SIZE)
         }
+        else {
+            validate::check_trailing(reader)?
+        };
+        let warnings: Vec<Issue> = issue.into_iter().collect();
+        payload.resize(expected, PADDING);
 
-        let mut chr_banks = Vec::<Vec::<u8>>::new();
-        for _ in 0..header.chr_rom_banks 
-        {
-            chr_banks.push(INESRom::read_bank(&mut reader, CHR_ROM_BANK_SIZE)?);
-        }
+        Ok(INESRom::from_storage(header, Storage::Owned(payload), 0, warnings))
+    }
 
-        let mut play_choice_bank = None;
-        if header.has_play_choice_10()
+    fn read_header(reader: &mut dyn Read) -> Result<INESHeader, Box<dyn Error>>
+    {
+        let header = INESHeader::from_reader(reader)?;
+        if header.format != [0x4E, 0x45, 0x53, 0x1A]
         {
-            play_choice_bank = Some(INESRom::read_bank(&mut reader, PLAY_CHOICE_10_SIZE)?);
+            return Err(Box::new(FormatError("Invalid format name".into())));
         }
 
-        Ok(INESRom { 
-            header: header,
-            trainer: trainer,
+        Ok(header)
+    }
+
+    // The payload in data starts at offset and must be at least as large as
+    // the header describes
+    fn from_storage(header: INESHeader, data: Storage, offset: usize, warnings: Vec<Issue>) -> Self
+    {
+        let mut pos = offset;
+        let mut next = |size: usize| {
+            pos += size;
+            pos - size..pos
+        };
+
+        let trainer = header.has_trainer().then(|| data[next(TRAINER_SIZE)].to_vec());
+        let prg_rom = next(PRG_ROM_BANK_SIZE * header.prg_rom_banks as usize);
+        let chr_rom = next(CHR_ROM_BANK_SIZE * header.chr_rom_banks as usize);
+        let play_choice_bank = header.has_play_choice_10().then(|| data[next(PLAY_CHOICE_10_SIZE)].to_vec());
+
+        INESRom { 
+            header,
+            trainer,
             play_chouice_10: play_choice_bank,
-            prg_banks: prg_banks,
-            chr_banks: chr_banks
-        })
+            data,
+            prg_rom,
+            chr_rom,
+            warnings
+        }
+    }
+
+    // Checks a ROM file for problems without loading it, so frontends can
+    // explain why a file doesn't load or may not run correctly
+    pub fn validate(reader: impl Read) -> Result<ValidationReport, Box<dyn Error>>
+    {
+        validate::validate(reader)
+    }
+
+    // Problems that were worked around while loading
+    pub fn get_warnings(&self) -> &[Issue]
+    {
+        &self.warnings
     }
 
     pub fn has_persistent_memory(&self) -> bool
@@ -361,6 +635,12 @@ impl INESRom
         self.header.get_mirroring()
     }
 
+    // Corrects the header of known bad dumps
+    pub fn set_mirroring(&mut self, mirroring: Mirroring)
+    {
+        self.header.set_mirroring(mirroring);
+    }
+
     pub fn get_ignore_mirroring(&self) -> bool
     {
         self.header.get_ignore_mirroring()
@@ -371,37 +651,205 @@ impl INESRom
         self.header.get_mapper()
     }
 
+    pub fn set_mapper(&mut self, mapper: u8)
+    {
+        self.header.set_mapper(mapper);
+    }
+
+    pub fn get_prg_ram_size(&self) -> usize
+    {
+        self.header.get_prg_ram_size()
+    }
+
+    pub fn get_prg_nvram_size(&self) -> usize
+    {
+        self.header.get_prg_nvram_size()
+    }
+
+    pub fn get_chr_ram_size(&self) -> usize
+    {
+        self.header.get_chr_ram_size()
+    }
+
+    pub fn get_chr_nvram_size(&self) -> usize
+    {
+        self.header.get_chr_nvram_size()
+    }
+
+    // Cartridge RAM sized from the header, the battery-backed part is what a
+    // frontend saves and loads
+    pub fn create_prg_ram(&self) -> CartridgeRam
+    {
+        CartridgeRam::new(self.get_prg_ram_size(), self.get_prg_nvram_size())
+    }
+
+    pub fn create_chr_ram(&self) -> CartridgeRam
+    {
+        CartridgeRam::new(self.get_chr_ram_size(), self.get_chr_nvram_size())
+    }
+
     pub fn get_trainer(&self) -> Option<&Vec<u8>>
     {
         self.trainer.as_ref()
     }
 
-    pub fn get_prg_bank(&self, index: usize) -> Option<&Vec<u8>>
+    pub fn get_prg_bank(&self, index: usize) -> Option<&[u8]>
     {
-        self.prg_banks.get(index)
+        self.prg_rom().chunks(PRG_ROM_BANK_SIZE).nth(index)
     }
 
-    pub fn get_chr_bank(&self, index: usize) -> Option<&Vec<u8>>
+    pub fn get_chr_bank(&self, index: usize) -> Option<&[u8]>
     {
-        self.chr_banks.get(index)
+        self.chr_rom().chunks(CHR_ROM_BANK_SIZE).nth(index)
     }
 
-    pub fn get_play_choise_10(&self) -> Option<&Vec<u8>>
+    pub fn prg_rom(&self) -> &[u8]
     {
-        self.play_chouice_10.as_ref()
+        &self.data[self.prg_rom.clone()]
+    }
+
+    pub fn chr_rom(&self) -> &[u8]
+    {
+        &self.data[self.chr_rom.clone()]
     }
 
-    fn read_bank(reader: &mut dyn Read, size: usize) -> Result<Vec<u8>, Box<dyn Error>>
+    pub fn prg_crc32(&self) -> u32
     {
-        let mut buf = Vec::with_capacity(size);
-        buf.resize(size, 0);
-        reader.read_exact(&mut buf)?;
-        Ok(buf)
+        hash::crc32(self.prg_rom())
+    }
+
+    pub fn chr_crc32(&self) -> u32
+    {
+        hash::crc32(self.chr_rom())
+    }
+
+    // SHA-1 of the PRG and CHR data without the header, as used by ROM databases
+    pub fn sha1(&self) -> [u8; 20]
+    {
+        let mut hasher = Sha1::new();
+        hasher.update(self.prg_rom());
+        hasher.update(self.chr_rom());
+        hasher.finish()
+    }
+
+    pub fn get_play_choise_10(&self) -> Option<&Vec<u8>>
+    {
+        self.play_chouice_10.as_ref()
     }
 }
 
 #[cfg(test)]
 mod tests
 {
-    
+    use std::io::Read;
+
+    use super::{INESRom, Issue};
+
+    fn rom_bytes(prg_banks: u8, chr_banks: u8) -> Vec<u8>
+    {
+        let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, prg_banks, chr_banks, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        for bank in 0..prg_banks {
+            bytes.extend(vec![bank; 0x4000]);
+        }
+        for bank in 0..chr_banks {
+            bytes.extend(vec![0x80 | bank; 0x2000]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn read()
+    {
+        let rom = INESRom::from_reader(&rom_bytes(2, 1)[..]).unwrap();
+
+        assert_eq!(0x8000, rom.prg_rom().len());
+        assert_eq!(0x2000, rom.chr_rom().len());
+        assert_eq!(Some(&[1u8; 0x4000][..]), rom.get_prg_bank(1));
+        assert_eq!(None, rom.get_prg_bank(2));
+        assert_eq!(Some(&[0x80u8; 0x2000][..]), rom.get_chr_bank(0));
+    }
+
+    #[test]
+    fn chr_ram()
+    {
+        let mut bytes = rom_bytes(1, 0);
+        let rom = INESRom::from_reader(&bytes[..]).unwrap();
+        assert_eq!(0x2000, rom.create_chr_ram().len());
+        assert!(!rom.create_chr_ram().is_persistent());
+
+        // NES 2.0 with 8K CHR-RAM and 2K of it battery-backed
+        bytes[7] = 0b00001000;
+        bytes[11] = 0x57;
+        let rom = INESRom::from_reader(&bytes[..]).unwrap();
+        let chr_ram = rom.create_chr_ram();
+        assert_eq!(0x2800, chr_ram.len());
+        assert_eq!(0x800, chr_ram.save().len());
+    }
+
+    #[test]
+    fn invalid_format()
+    {
+        let mut bytes = rom_bytes(1, 0);
+        bytes[3] = 0;
+
+        assert!(INESRom::from_reader(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn truncated_padded()
+    {
+        let bytes = rom_bytes(2, 1);
+        let rom = INESRom::from_reader(&bytes[..0x5000]).unwrap();
+
+        assert_eq!(0x8000, rom.prg_rom().len());
+        assert_eq!(0x2000, rom.chr_rom().len());
+        assert_eq!(1, rom.prg_rom()[0x4FEF]);
+        assert_eq!(0xFF, rom.prg_rom()[0x4FF0]);
+        assert_eq!(0xFF, rom.chr_rom()[0]);
+        assert_eq!(&[Issue::Truncated { expected: 0xA010, actual: 0x5000 }], rom.get_warnings());
+    }
+
+    #[test]
+    fn trailing_data_truncated()
+    {
+        let mut bytes = rom_bytes(1, 1);
+        bytes.extend([0x12; 0x100]);
+        let rom = INESRom::from_reader(&bytes[..]).unwrap();
+
+        assert_eq!(0x4000, rom.prg_rom().len());
+        assert_eq!(0x2000, rom.chr_rom().len());
+        assert_eq!(&[Issue::TrailingData { len: 0x100, padding: false }], rom.get_warnings());
+    }
+
+    #[test]
+    fn trailing_stream_counted()
+    {
+        let bytes = rom_bytes(1, 0);
+        let rom = INESRom::from_reader((&bytes[..]).chain(std::io::repeat(0xFF).take(0x100000))).unwrap();
+
+        assert_eq!(0x4000, rom.prg_rom().len());
+        assert_eq!(&[Issue::TrailingData { len: 0x100000, padding: true }], rom.get_warnings());
+    }
+
+    #[test]
+    fn no_warnings()
+    {
+        let rom = INESRom::from_reader(&rom_bytes(1, 1)[..]).unwrap();
+
+        assert!(rom.get_warnings().is_empty());
+    }
+
+    #[test]
+    fn hashes()
+    {
+        let rom = INESRom::from_reader(&rom_bytes(1, 1)[..]).unwrap();
+
+        assert_eq!(super::hash::crc32(&[0; 0x4000]), rom.prg_crc32());
+        assert_eq!(super::hash::crc32(&[0x80; 0x2000]), rom.chr_crc32());
+        assert_ne!(rom.prg_crc32(), rom.chr_crc32());
+
+        let mut hasher = super::hash::Sha1::new();
+        hasher.update(&rom_bytes(1, 1)[16..]);
+        assert_eq!(hasher.finish(), rom.sha1());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,209 @@
+use std::error::Error;
+
+use serde::Deserialize;
+
+use crate::bus::Bus;
+use crate::joypad::{Joypad, Peripheral, PowerPad};
+use super::{INESRom, Mirroring};
+
+// Device a game expects in the second controller port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputDevice
+{
+    None,
+    Joypad,
+    PowerPad
+}
+
+impl InputDevice
+{
+    pub fn to_peripheral(self) -> Peripheral
+    {
+        match self {
+            InputDevice::None => Peripheral::None,
+            InputDevice::Joypad => Peripheral::Joypad(Joypad::new()),
+            InputDevice::PowerPad => Peripheral::PowerPad(PowerPad::new())
+        }
+    }
+}
+
+// Header corrections for one game, matched by the SHA-1 of its PRG and CHR
+// data as reported by INESRom::sha1()
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GameOverride
+{
+    pub sha1: String,
+    pub mapper: Option<u8>,
+    pub mirroring: Option<Mirroring>,
+    pub input: Option<InputDevice>
+}
+
+impl GameOverride
+{
+    // Plugs the overridden device into port 1 ($4017), where accessories like
+    // the Power Pad go. Port 0 keeps its controller.
+    pub fn connect(&self, bus: &mut Bus)
+    {
+        if let Some(input) = self.input {
+            bus.connect(1, input.to_peripheral());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OverrideFile
+{
+    #[serde(default)]
+    game: Vec<GameOverride>
+}
+
+// User-supplied per-game overrides, loaded from TOML:
+//
+//   [[game]]
+//   sha1 = "0123456789abcdef0123456789abcdef01234567"
+//   mapper = 4
+//   mirroring = "vertical"
+//   input = "powerpad"
+//
+// Mirroring is "horizontal", "vertical" or "four-screen", input is "joypad",
+// "powerpad" or "none".
+#[derive(Debug, Clone, Default)]
+pub struct OverrideDatabase
+{
+    games: Vec<GameOverride>
+}
+
+impl OverrideDatabase
+{
+    pub fn from_toml(text: &str) -> Result<OverrideDatabase, Box<dyn Error>>
+    {
+        let file: OverrideFile = toml::from_str(text)?;
+        let mut games = file.game;
+        for game in &mut games {
+            game.sha1.make_ascii_lowercase();
+            if game.sha1.len() != 40 || !game.sha1.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("Invalid SHA-1 in game override: {}", game.sha1).into());
+            }
+        }
+
+        Ok(OverrideDatabase { games })
+    }
+
+    pub fn find(&self, rom: &INESRom) -> Option<&GameOverride>
+    {
+        let sha1: String = rom.sha1().iter().map(|b| format!("{:02x}", b)).collect();
+        self.games.iter().find(|game| game.sha1 == sha1)
+    }
+
+    // Applies the header fixes of the override for the ROM, if there is one,
+    // and returns it. The input device is set up separately with connect().
+    pub fn apply(&self, rom: &mut INESRom) -> Option<&GameOverride>
+    {
+        let game = self.find(rom)?;
+        if let Some(mapper) = game.mapper {
+            rom.set_mapper(mapper);
+        }
+        if let Some(mirroring) = game.mirroring {
+            rom.set_mirroring(mirroring);
+        }
+        Some(game)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{InputDevice, OverrideDatabase};
+    use crate::bus::Bus;
+    use crate::joypad::Peripheral;
+    use crate::rom::{INESRom, Mirroring};
+
+    fn rom() -> INESRom
+    {
+        let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        bytes.extend([0u8; 0x4000]);
+        INESRom::from_reader(&bytes[..]).unwrap()
+    }
+
+    fn sha1_hex(rom: &INESRom) -> String
+    {
+        rom.sha1().iter().map(|b| format!("{:02X}", b)).collect()
+    }
+
+    #[test]
+    fn apply()
+    {
+        let mut rom = rom();
+        let text = format!("[[game]]\nsha1 = \"{}\"\nmapper = 209\nmirroring = \"vertical\"\n", sha1_hex(&rom));
+        let db = OverrideDatabase::from_toml(&text).unwrap();
+
+        assert!(db.apply(&mut rom).is_some());
+        assert_eq!(209, rom.get_mapper());
+        assert_eq!(Mirroring::Vertical, rom.get_mirroring());
+    }
+
+    #[test]
+    fn partial_override()
+    {
+        let mut rom = rom();
+        rom.set_mapper(4);
+        let text = format!("[[game]]\nsha1 = \"{}\"\nmirroring = \"vertical\"\n", sha1_hex(&rom));
+        OverrideDatabase::from_toml(&text).unwrap().apply(&mut rom);
+
+        assert_eq!(4, rom.get_mapper());
+        assert_eq!(Mirroring::Vertical, rom.get_mirroring());
+    }
+
+    #[test]
+    fn four_screen_and_power_pad()
+    {
+        let mut rom = rom();
+        let text = format!("[[game]]\nsha1 = \"{}\"\nmirroring = \"four-screen\"\ninput = \"powerpad\"\n", sha1_hex(&rom));
+        let db = OverrideDatabase::from_toml(&text).unwrap();
+
+        let game = db.apply(&mut rom).unwrap();
+        assert_eq!(Some(InputDevice::PowerPad), game.input);
+        assert_eq!(Mirroring::FourScreen, rom.get_mirroring());
+        assert!(rom.get_ignore_mirroring());
+
+        let mut bus = Bus::new();
+        game.connect(&mut bus);
+        assert!(matches!(bus.get_peripheral(1), Peripheral::PowerPad(_)));
+        assert!(matches!(bus.get_peripheral(0), Peripheral::Joypad(_)));
+    }
+
+    #[test]
+    fn no_input_keeps_ports()
+    {
+        let mut rom = rom();
+        let text = format!("[[game]]\nsha1 = \"{}\"\nmapper = 1\n", sha1_hex(&rom));
+        let db = OverrideDatabase::from_toml(&text).unwrap();
+
+        let mut bus = Bus::new();
+        db.apply(&mut rom).unwrap().connect(&mut bus);
+        assert!(matches!(bus.get_peripheral(1), Peripheral::Joypad(_)));
+    }
+
+    #[test]
+    fn no_match()
+    {
+        let mut rom = rom();
+        let db = OverrideDatabase::from_toml("[[game]]\nsha1 = \"0000000000000000000000000000000000000000\"\nmapper = 1\n").unwrap();
+
+        assert!(db.apply(&mut rom).is_none());
+        assert_eq!(0, rom.get_mapper());
+        assert!(OverrideDatabase::from_toml("").unwrap().find(&rom).is_none());
+    }
+
+    #[test]
+    fn invalid()
+    {
+        assert!(OverrideDatabase::from_toml("[[game]]\nsha1 = \"abc\"\n").is_err());
+        assert!(OverrideDatabase::from_toml("[[game]]\nsha1 = \"0000000000000000000000000000000000000000\"\nregion = \"pal\"\n").is_err());
+        assert!(OverrideDatabase::from_toml("[[game]]\nsha1 = \"0000000000000000000000000000000000000000\"\nmirroring = \"diagonal\"\n").is_err());
+        assert!(OverrideDatabase::from_toml("[[game]]\nsha1 = \"0000000000000000000000000000000000000000\"\ninput = \"zapper\"\n").is_err());
+    }
+}
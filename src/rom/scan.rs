@@ -0,0 +1,195 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use super::hash::Crc32;
+use super::{get_mapper_name, INESRom, Mirroring};
+use super::{CHR_ROM_BANK_SIZE, PRG_ROM_BANK_SIZE, TRAINER_SIZE};
+
+// Header metadata of a ROM file, as listed by ROM managers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo
+{
+    pub mapper: u8,
+    pub mapper_name: Option<&'static str>,
+    pub submapper: Option<u8>,
+    pub mirroring: Mirroring,
+    pub nes2: bool,
+    pub has_trainer: bool,
+    pub has_persistent_memory: bool,
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    // CRC-32 of PRG and CHR data, only filled in when hashing was requested
+    pub crc32: Option<u32>
+}
+
+// Reads the ROM header without loading the payload. With hash set, the PRG
+// and CHR data is streamed through CRC-32 instead of being kept in memory.
+pub fn scan(mut reader: impl Read, hash: bool) -> Result<RomInfo, Box<dyn Error>>
+{
+    let header = INESRom::read_header(&mut reader)?;
+    let prg_rom_size = PRG_ROM_BANK_SIZE * header.prg_rom_banks as usize;
+    let chr_rom_size = CHR_ROM_BANK_SIZE * header.chr_rom_banks as usize;
+
+    let mut crc32 = None;
+    if hash {
+        if header.has_trainer() {
+            io::copy(&mut (&mut reader).take(TRAINER_SIZE as u64), &mut io::sink())?;
+        }
+
+        let mut hasher = Crc32::new();
+        let mut payload = reader.take((prg_rom_size + chr_rom_size) as u64);
+        let mut chunk = [0; 0x2000];
+        loop {
+            let len = payload.read(&mut chunk)?;
+            if len == 0 {
+                break;
+            }
+            hasher.update(&chunk[..len]);
+        }
+        crc32 = Some(hasher.finish());
+    }
+
+    Ok(RomInfo {
+        mapper: header.get_mapper(),
+        mapper_name: get_mapper_name(header.get_mapper()),
+        submapper: header.get_submapper(),
+        mirroring: header.get_mirroring(),
+        nes2: header.is_nes2_format(),
+        has_trainer: header.has_trainer(),
+        has_persistent_memory: header.has_persistent_memory(),
+        prg_rom_size,
+        chr_rom_size,
+        prg_ram_size: header.get_prg_ram_size(),
+        prg_nvram_size: header.get_prg_nvram_size(),
+        chr_ram_size: header.get_chr_ram_size(),
+        chr_nvram_size: header.get_chr_nvram_size(),
+        crc32
+    })
+}
+
+// Scans every .nes file below path. Files that fail to parse are returned
+// with their error so the caller can list them, only I/O errors while walking
+// the directory abort the scan. Symlinked directories are not followed, so a
+// link back up the tree can't make the walk go round forever.
+#[allow(clippy::type_complexity)]
+pub fn scan_dir(path: impl AsRef<Path>, hash: bool) -> io::Result<Vec<(PathBuf, Result<RomInfo, Box<dyn Error>>)>>
+{
+    let mut results = Vec::new();
+    let mut dirs = vec![path.as_ref().to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = std::fs::read_dir(&dir)?
+            .map(|entry| entry.and_then(|entry| Ok((entry.path(), entry.file_type()?))))
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        for (entry, file_type) in entries {
+            if file_type.is_dir() {
+                dirs.push(entry);
+            }
+            else if is_nes_file(&entry) {
+                let info = File::open(&entry)
+                    .map_err(|err| err.into())
+                    .and_then(|file| scan(BufReader::new(file), hash));
+                results.push((entry, info));
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+fn is_nes_file(path: &Path) -> bool
+{
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("nes"))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{scan, scan_dir};
+    use crate::rom::hash::crc32;
+    use crate::rom::Mirroring;
+
+    fn rom_bytes(prg_banks: u8, chr_banks: u8, flag6: u8) -> Vec<u8>
+    {
+        let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, prg_banks, chr_banks, flag6, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        bytes.extend(vec![0x11; 0x4000 * prg_banks as usize]);
+        bytes.extend(vec![0x22; 0x2000 * chr_banks as usize]);
+        bytes
+    }
+
+    #[test]
+    fn header_only()
+    {
+        let bytes = rom_bytes(2, 1, 0x11);
+        let info = scan(&bytes[..0x10], false).unwrap();
+
+        assert_eq!(1, info.mapper);
+        assert_eq!(Some("MMC1"), info.mapper_name);
+        assert_eq!(Mirroring::Vertical, info.mirroring);
+        assert_eq!(0x8000, info.prg_rom_size);
+        assert_eq!(0x2000, info.chr_rom_size);
+        assert_eq!(0, info.chr_ram_size);
+        assert_eq!(None, info.submapper);
+        assert_eq!(None, info.crc32);
+    }
+
+    #[test]
+    fn hash_skips_trainer_and_trailing_data()
+    {
+        let mut bytes = rom_bytes(1, 1, 0b100);
+        bytes.splice(0x10..0x10, vec![0x33; 0x200]);
+        bytes.extend([0x44; 0x10]);
+        let info = scan(&bytes[..], true).unwrap();
+
+        assert_eq!(Some(crc32(&bytes[0x210..0x6210])), info.crc32);
+    }
+
+    #[test]
+    fn invalid_format()
+    {
+        assert!(scan(&[0u8; 16][..], false).is_err());
+    }
+
+    #[test]
+    fn directory()
+    {
+        let dir = std::env::temp_dir().join(format!("nesemu-scan-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.nes"), rom_bytes(1, 0, 0)).unwrap();
+        std::fs::write(dir.join("sub").join("b.NES"), [0u8; 4]).unwrap();
+        std::fs::write(dir.join("readme.txt"), "not a rom").unwrap();
+
+        let results = scan_dir(&dir, false).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(2, results.len());
+        assert_eq!(dir.join("a.nes"), results[0].0);
+        assert_eq!(0x2000, results[0].1.as_ref().unwrap().chr_ram_size);
+        assert!(results[1].1.is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cycle()
+    {
+        let dir = std::env::temp_dir().join(format!("nesemu-scan-cycle-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub").join("a.nes"), rom_bytes(1, 0, 0)).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("sub").join("up")).unwrap();
+        std::os::unix::fs::symlink(".", dir.join("self")).unwrap();
+
+        let results = scan_dir(&dir, false);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let results = results.unwrap();
+        assert_eq!(1, results.len());
+        assert_eq!(dir.join("sub").join("a.nes"), results[0].0);
+    }
+}
@@ -0,0 +1,291 @@
+use std::error::Error;
+use std::io::{self, Read};
+
+use super::header::INESHeader;
+use super::{TRAINER_SIZE, PRG_ROM_BANK_SIZE, CHR_ROM_BANK_SIZE, PLAY_CHOICE_10_SIZE};
+
+pub const HEADER_SIZE: usize = 16;
+
+const KNOWN_MAPPERS: [(u8, &str); 29] = [
+    (0, "NROM"),
+    (1, "MMC1"),
+    (2, "UxROM"),
+    (3, "CNROM"),
+    (4, "MMC3"),
+    (5, "MMC5"),
+    (7, "AxROM"),
+    (9, "MMC2"),
+    (10, "MMC4"),
+    (11, "Color Dreams"),
+    (16, "Bandai FCG"),
+    (19, "Namco 163"),
+    (21, "VRC4"),
+    (23, "VRC2/VRC4"),
+    (24, "VRC6"),
+    (28, "Action 53"),
+    (30, "UNROM 512"),
+    (34, "BNROM/NINA-001"),
+    (66, "GxROM"),
+    (69, "Sunsoft FME-7"),
+    (71, "Camerica"),
+    (76, "Namco 3446"),
+    (79, "NINA-03/06"),
+    (88, "Namco 3433"),
+    (95, "Namco 3425"),
+    (111, "GTROM"),
+    (140, "Jaleco JF-11/14"),
+    (154, "Namco 3453"),
+    (206, "Namco 108")
+];
+
+const MAX_ALTERNATIVES: usize = 4;
+
+pub fn get_mapper_name(mapper: u8) -> Option<&'static str>
+{
+    KNOWN_MAPPERS.iter().find(|(id, _)| *id == mapper).map(|(_, name)| *name)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Issue
+{
+    InvalidFormat,
+    Truncated { expected: usize, actual: usize },
+    TrailingData { len: usize, padding: bool },
+    // iNES 1.0 header with garbage in the unused bytes, usually a ripper
+    // signature such as "DiskDude!" that also corrupts the upper mapper bits
+    DirtyHeader,
+    UnknownMapper { mapper: u8, alternatives: Vec<u8> }
+}
+
+#[derive(Debug, Default)]
+pub struct ValidationReport
+{
+    pub issues: Vec<Issue>
+}
+
+impl ValidationReport
+{
+    pub fn is_valid(&self) -> bool
+    {
+        self.issues.is_empty()
+    }
+}
+
+pub fn validate(mut reader: impl Read) -> Result<ValidationReport, Box<dyn Error>>
+{
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let mut report = ValidationReport::default();
+    if data.len() < HEADER_SIZE {
+        report.issues.push(Issue::Truncated { expected: HEADER_SIZE, actual: data.len() });
+        return Ok(report);
+    }
+
+    let header = INESHeader::from_reader(&mut &data[..HEADER_SIZE])?;
+    if header.format != [0x4E, 0x45, 0x53, 0x1A] {
+        report.issues.push(Issue::InvalidFormat);
+        return Ok(report);
+    }
+
+    if let Some(issue) = check_size(&data, get_expected_size(&header), 0) {
+        report.issues.push(issue);
+    }
+
+    let dirty = !header.is_nes2_format() && data[12..HEADER_SIZE].iter().any(|b| *b != 0);
+    if dirty {
+        report.issues.push(Issue::DirtyHeader);
+    }
+
+    let mapper = header.get_mapper();
+    if get_mapper_name(mapper).is_none() {
+        report.issues.push(Issue::UnknownMapper { mapper, alternatives: get_alternatives(mapper, dirty) });
+    }
+
+    Ok(report)
+}
+
+// Known mappers an unknown id most likely stands for, best guess first. A
+// dirty header corrupts the upper nibble, so the lower one alone is the best
+// guess there. Otherwise boards of one family tend to have neighbouring
+// numbers (VRC2/VRC4 variants at 21-25), and a single bad upper nibble still
+// leaves the lower one intact.
+fn get_alternatives(mapper: u8, dirty: bool) -> Vec<u8>
+{
+    let by_distance = |filter: &dyn Fn(u8) -> bool| {
+        let mut ids: Vec<u8> = KNOWN_MAPPERS.iter().map(|(id, _)| *id).filter(|id| filter(*id)).collect();
+        ids.sort_by_key(|id| id.abs_diff(mapper));
+        ids
+    };
+
+    let mut alternatives = Vec::new();
+    if dirty && get_mapper_name(mapper & 0x0F).is_some() {
+        alternatives.push(mapper & 0x0F);
+    }
+    alternatives.extend(by_distance(&|id| id.abs_diff(mapper) <= 2));
+    alternatives.extend(by_distance(&|id| id & 0x0F == mapper & 0x0F));
+
+    let mut seen = Vec::new();
+    alternatives.retain(|id| {
+        let first = !seen.contains(id);
+        seen.push(*id);
+        first
+    });
+    alternatives.truncate(MAX_ALTERNATIVES);
+    alternatives
+}
+
+// Compares data with the size the header describes. Sizes in the issue are
+// reported relative to the start of the file, data starts at offset.
+pub fn check_size(data: &[u8], expected: usize, offset: usize) -> Option<Issue>
+{
+    if data.len() < expected {
+        return Some(Issue::Truncated { expected: expected + offset, actual: data.len() + offset });
+    }
+
+    if data.len() > expected {
+        let trailing = &data[expected..];
+        let padding = trailing.iter().all(|b| *b == 0x00) || trailing.iter().all(|b| *b == 0xFF);
+        return Some(Issue::TrailingData { len: trailing.len(), padding });
+    }
+
+    None
+}
+
+// Same as check_size for the data following the expected size, read from a
+// stream in small chunks so an oversized dump is never held in memory
+pub fn check_trailing(mut reader: impl Read) -> io::Result<Option<Issue>>
+{
+    let mut buf = [0; 0x1000];
+    let mut len = 0;
+    let (mut zeros, mut ones) = (true, true);
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err)
+        };
+
+        len += n;
+        zeros &= buf[..n].iter().all(|b| *b == 0x00);
+        ones &= buf[..n].iter().all(|b| *b == 0xFF);
+    }
+
+    Ok((len > 0).then_some(Issue::TrailingData { len, padding: zeros || ones }))
+}
+
+pub fn get_expected_size(header: &INESHeader) -> usize
+{
+    let mut size = HEADER_SIZE
+        + PRG_ROM_BANK_SIZE * header.prg_rom_banks as usize
+        + CHR_ROM_BANK_SIZE * header.chr_rom_banks as usize;
+
+    if header.has_trainer() {
+        size += TRAINER_SIZE;
+    }
+    if header.has_play_choice_10() {
+        size += PLAY_CHOICE_10_SIZE;
+    }
+
+    size
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn rom_bytes(flag6: u8, flag7: u8) -> Vec<u8>
+    {
+        let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, flag6, flag7, 0, 0, 0, 0, 0, 0, 0, 0];
+        bytes.resize(HEADER_SIZE + 0x4000 + 0x2000, 0xEA);
+        bytes
+    }
+
+    #[test]
+    fn valid()
+    {
+        let report = validate(&rom_bytes(0, 0)[..]).unwrap();
+
+        assert!(report.is_valid());
+    }
+
+    #[test]
+    fn invalid_format()
+    {
+        let mut bytes = rom_bytes(0, 0);
+        bytes[0] = 0;
+
+        assert_eq!(vec![Issue::InvalidFormat], validate(&bytes[..]).unwrap().issues);
+    }
+
+    #[test]
+    fn truncated()
+    {
+        let bytes = rom_bytes(0, 0);
+        let report = validate(&bytes[..0x1000]).unwrap();
+
+        assert_eq!(vec![Issue::Truncated { expected: 0x6010, actual: 0x1000 }], report.issues);
+        assert_eq!(vec![Issue::Truncated { expected: 16, actual: 3 }], validate(&bytes[..3]).unwrap().issues);
+    }
+
+    #[test]
+    fn trailing_data()
+    {
+        let mut bytes = rom_bytes(0, 0);
+        bytes.extend([0xFF; 0x100]);
+        assert_eq!(vec![Issue::TrailingData { len: 0x100, padding: true }], validate(&bytes[..]).unwrap().issues);
+
+        bytes.push(0x12);
+        assert_eq!(vec![Issue::TrailingData { len: 0x101, padding: false }], validate(&bytes[..]).unwrap().issues);
+    }
+
+    #[test]
+    fn dirty_header()
+    {
+        let mut bytes = rom_bytes(0x40, 0x44);
+        bytes[7..16].copy_from_slice(b"DiskDude!");
+
+        assert_eq!(vec![
+            Issue::DirtyHeader,
+            Issue::UnknownMapper { mapper: 0x44, alternatives: vec![4, 69, 66] }
+        ], validate(&bytes[..]).unwrap().issues);
+    }
+
+    #[test]
+    fn unknown_mapper()
+    {
+        let bytes = rom_bytes(0xF0, 0xF0);
+
+        assert_eq!(vec![Issue::UnknownMapper { mapper: 0xFF, alternatives: vec![111, 95, 79] }], validate(&bytes[..]).unwrap().issues);
+    }
+
+    #[test]
+    fn unknown_mapper_neighbours()
+    {
+        // Mapper 22, a VRC2 variant
+        let bytes = rom_bytes(0x60, 0x10);
+        assert_eq!(vec![Issue::UnknownMapper { mapper: 22, alternatives: vec![21, 23, 24] }], validate(&bytes[..]).unwrap().issues);
+
+        // Mapper 200, only the lower nibble matches anything known
+        let bytes = rom_bytes(0x80, 0xC0);
+        assert_eq!(vec![Issue::UnknownMapper { mapper: 200, alternatives: vec![88, 24] }], validate(&bytes[..]).unwrap().issues);
+    }
+
+    #[test]
+    fn namco_and_jaleco_boards()
+    {
+        for mapper in [76, 88, 95, 140, 154] {
+            let bytes = rom_bytes(mapper << 4, mapper & 0xF0);
+            assert!(validate(&bytes[..]).unwrap().is_valid(), "mapper {}", mapper);
+        }
+    }
+
+    #[test]
+    fn mapper_names()
+    {
+        assert_eq!(Some("MMC3"), get_mapper_name(4));
+        assert_eq!(None, get_mapper_name(255));
+    }
+}
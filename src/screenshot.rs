@@ -0,0 +1,226 @@
+use std::error::Error;
+use std::io::{self, Read, Write};
+
+use crate::palette::Color;
+
+const MISMATCH_COLOR: Color = [0xFF, 0x00, 0x00];
+
+// RGB image, used for frames and the reference captures they're checked against
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image
+{
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>
+}
+
+impl Image
+{
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Result<Image, Box<dyn Error>>
+    {
+        let size = width.checked_mul(height).ok_or_else(|| format!("{}x{} image is too large", width, height))?;
+        if pixels.len() != size {
+            return Err(format!("{}x{} image needs {} pixels, got {}", width, height, size, pixels.len()).into());
+        }
+
+        Ok(Image { width, height, pixels })
+    }
+
+    // Reads a binary (P6) PPM image with 8-bit channels
+    pub fn from_ppm(mut reader: impl Read) -> Result<Image, Box<dyn Error>>
+    {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        let mut pos = 0;
+        let mut fields = [0usize; 3];
+        if next_token(&data, &mut pos) != b"P6" {
+            return Err("Not a binary PPM image".into());
+        }
+        for field in fields.iter_mut() {
+            *field = std::str::from_utf8(next_token(&data, &mut pos))?.parse()?;
+        }
+
+        let [width, height, max_val] = fields;
+        if max_val != 255 {
+            return Err(format!("Unsupported PPM max value {}", max_val).into());
+        }
+
+        let len = width.checked_mul(height)
+            .and_then(|size| size.checked_mul(3))
+            .ok_or_else(|| format!("{}x{} PPM image is too large", width, height))?;
+
+        // A single whitespace byte separates the header from the pixels
+        let pixels = data.get(pos + 1..).unwrap_or_default();
+        if pixels.len() < len {
+            return Err("PPM image is truncated".into());
+        }
+
+        let pixels = pixels[..len].chunks_exact(3).map(|rgb| [rgb[0], rgb[1], rgb[2]]).collect();
+        Image::new(width, height, pixels)
+    }
+
+    pub fn write_ppm(&self, writer: &mut dyn Write) -> io::Result<()>
+    {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for pixel in &self.pixels {
+            writer.write_all(pixel)?;
+        }
+        Ok(())
+    }
+
+    pub fn get_width(&self) -> usize
+    {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize
+    {
+        self.height
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> Color
+    {
+        self.pixels[y * self.width + x]
+    }
+}
+
+fn next_token<'a>(data: &'a [u8], pos: &mut usize) -> &'a [u8]
+{
+    loop {
+        while *pos < data.len() && data[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if *pos < data.len() && data[*pos] == b'#' {
+            while *pos < data.len() && data[*pos] != b'\n' {
+                *pos += 1;
+            }
+            continue;
+        }
+        break;
+    }
+
+    let start = *pos;
+    while *pos < data.len() && !data[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    &data[start..*pos]
+}
+
+// Result of comparing a frame with its reference, the mask marks pixels
+// where any channel differs by more than the tolerance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameDiff
+{
+    width: usize,
+    height: usize,
+    mask: Vec<bool>,
+    mismatched: usize
+}
+
+impl FrameDiff
+{
+    pub fn is_match(&self) -> bool
+    {
+        self.mismatched == 0
+    }
+
+    pub fn get_mismatched(&self) -> usize
+    {
+        self.mismatched
+    }
+
+    pub fn get_mask(&self) -> &[bool]
+    {
+        &self.mask
+    }
+
+    // Mismatched pixels in red on black, to store next to failing captures
+    pub fn to_image(&self) -> Image
+    {
+        let pixels = self.mask.iter().map(|diff| if *diff { MISMATCH_COLOR } else { [0; 3] }).collect();
+        Image { width: self.width, height: self.height, pixels }
+    }
+}
+
+pub fn compare(actual: &Image, expected: &Image, tolerance: u8) -> Result<FrameDiff, Box<dyn Error>>
+{
+    if actual.width != expected.width || actual.height != expected.height {
+        return Err(format!("Frame is {}x{}, reference is {}x{}",
+            actual.width, actual.height, expected.width, expected.height).into());
+    }
+
+    let mask: Vec<bool> = actual.pixels.iter()
+        .zip(&expected.pixels)
+        .map(|(a, e)| a.iter().zip(e).any(|(a, e)| a.abs_diff(*e) > tolerance))
+        .collect();
+    let mismatched = mask.iter().filter(|diff| **diff).count();
+
+    Ok(FrameDiff { width: actual.width, height: actual.height, mask, mismatched })
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{compare, Image};
+
+    fn image(pixels: &[[u8; 3]]) -> Image
+    {
+        Image::new(2, 2, pixels.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn ppm_round_trip()
+    {
+        let img = image(&[[1, 2, 3], [4, 5, 6], [7, 8, 9], [10, 11, 12]]);
+        let mut out = Vec::new();
+        img.write_ppm(&mut out).unwrap();
+
+        assert!(out.starts_with(b"P6\n2 2\n255\n"));
+        assert_eq!(img, Image::from_ppm(&out[..]).unwrap());
+    }
+
+    #[test]
+    fn ppm_with_comment()
+    {
+        let mut data = b"P6\n# made by hand\n1 1 255\n".to_vec();
+        data.extend([9, 8, 7]);
+
+        assert_eq!([9, 8, 7], Image::from_ppm(&data[..]).unwrap().get_pixel(0, 0));
+        assert!(Image::from_ppm(&data[..data.len() - 1]).is_err());
+        assert!(Image::from_ppm(&b"P3\n1 1 255\n0 0 0"[..]).is_err());
+    }
+
+    #[test]
+    fn oversized_dimensions()
+    {
+        let huge = usize::MAX / 2 + 1;
+
+        assert!(Image::new(huge, 2, Vec::new()).is_err());
+        assert!(Image::from_ppm(format!("P6\n{} 2 255\n\0\0\0", huge).as_bytes()).is_err());
+        assert!(Image::from_ppm(format!("P6\n{} 1 255\n\0\0\0", usize::MAX / 3 + 1).as_bytes()).is_err());
+    }
+
+    #[test]
+    fn compare_with_tolerance()
+    {
+        let expected = image(&[[100, 100, 100]; 4]);
+        let actual = image(&[[100, 100, 100], [102, 100, 100], [100, 97, 100], [100, 100, 100]]);
+
+        assert!(compare(&actual, &expected, 3).unwrap().is_match());
+
+        let diff = compare(&actual, &expected, 2).unwrap();
+        assert_eq!(1, diff.get_mismatched());
+        assert_eq!(&[false, false, true, false], diff.get_mask());
+        assert_eq!([0xFF, 0, 0], diff.to_image().get_pixel(0, 1));
+    }
+
+    #[test]
+    fn compare_size_mismatch()
+    {
+        let small = Image::new(1, 1, vec![[0; 3]]).unwrap();
+
+        assert!(compare(&small, &image(&[[0; 3]; 4]), 0).is_err());
+        assert!(Image::new(2, 2, vec![[0; 3]]).is_err());
+    }
+}
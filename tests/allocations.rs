@@ -0,0 +1,63 @@
+// Checks that the emulation loop doesn't touch the heap once it is running
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use nesemu::bus::Bus;
+use nesemu::cpu::CPU;
+use nesemu::debugger::memory_map::Region;
+
+struct CountingAllocator;
+
+thread_local! {
+    // Per thread, so the test harness allocating elsewhere doesn't count
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8
+    {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout)
+    {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8
+    {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize
+{
+    ALLOCATIONS.with(|count| count.get())
+}
+
+#[test]
+fn steady_state()
+{
+    // ADC #1, STA $10, STA $0300, JSR $0040, JMP $0000
+    // $0040: ADC $10, STA $11, RTS
+    let mut bus = Bus::new();
+    bus.write_buffer(0, &[0x69, 0x01, 0x85, 0x10, 0x8D, 0x00, 0x03, 0x20, 0x40, 0x00, 0x4C, 0x00, 0x00]);
+    bus.write_buffer(0x40, &[0x65, 0x10, 0x85, 0x11, 0x60]);
+    bus.set_access_trace(Region::Ram, 64);
+
+    let mut cpu = CPU::new(Box::new(bus));
+    cpu.set_trace_capacity(64);
+    cpu.set_journal_capacity(64);
+    cpu.ticks(1000).unwrap();
+
+    let before = allocations();
+    cpu.ticks(100_000).unwrap();
+
+    assert_eq!(before, allocations());
+}
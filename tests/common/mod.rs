@@ -0,0 +1,24 @@
+// Helpers shared by the integration tests
+
+use nesemu::bus::BusInterface;
+
+// Flat 64K RAM without any NES devices
+pub struct Flat(pub Vec<u8>);
+
+impl BusInterface for Flat
+{
+    fn read8(&mut self, addr: u16) -> u8
+    {
+        self.0[addr as usize]
+    }
+
+    fn write8(&mut self, addr: u16, val: u8)
+    {
+        self.0[addr as usize] = val;
+    }
+
+    fn peek8(&self, addr: u16) -> u8
+    {
+        self.0[addr as usize]
+    }
+}
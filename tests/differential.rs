@@ -0,0 +1,162 @@
+// Runs random instruction sequences on the CPU and on a simple interpretive
+// 6502 model and compares the results. The model only covers the opcodes the
+// CPU implements, extend both together. Cycle timing is not compared.
+
+mod common;
+
+use common::Flat;
+use nesemu::cpu::CPU;
+use proptest::prelude::*;
+
+const PROGRAM_START: u16 = 0x8000;
+
+mod flag
+{
+    pub const C: u8 = 0b00000001;
+    pub const Z: u8 = 0b00000010;
+    pub const V: u8 = 0b01000000;
+    pub const N: u8 = 0b10000000;
+}
+
+#[derive(Debug, Clone)]
+enum Instr
+{
+    Nop,
+    Jmp,
+    AdcImm(u8),
+    AdcZp(u8),
+    AdcZpx(u8),
+    StaZp(u8),
+    StaZpx(u8)
+}
+
+// Encodes instructions so they run one after another, JMP targets the
+// instruction following it
+fn assemble(program: &[Instr]) -> Vec<u8>
+{
+    let mut bytes = Vec::new();
+    for instr in program {
+        match instr {
+            Instr::Nop => bytes.push(0xEA),
+            Instr::Jmp => {
+                let target = PROGRAM_START + bytes.len() as u16 + 3;
+                bytes.extend([0x4C, target as u8, (target >> 8) as u8]);
+            },
+            Instr::AdcImm(val) => bytes.extend([0x69, *val]),
+            Instr::AdcZp(addr) => bytes.extend([0x65, *addr]),
+            Instr::AdcZpx(addr) => bytes.extend([0x75, *addr]),
+            Instr::StaZp(addr) => bytes.extend([0x85, *addr]),
+            Instr::StaZpx(addr) => bytes.extend([0x95, *addr])
+        }
+    }
+    bytes
+}
+
+struct Reference
+{
+    mem: Vec<u8>,
+    pc: u16,
+    a: u8,
+    x: u8,
+    p: u8
+}
+
+impl Reference
+{
+    fn step(&mut self)
+    {
+        let op_code = self.mem[self.pc as usize];
+        let arg = self.mem[self.pc.wrapping_add(1) as usize];
+        match op_code {
+            0xEA => self.pc += 1,
+            0x4C => self.pc = u16::from_le_bytes([arg, self.mem[self.pc as usize + 2]]),
+            0x69 => {
+                self.adc(arg);
+                self.pc += 2;
+            },
+            0x65 => {
+                self.adc(self.mem[arg as usize]);
+                self.pc += 2;
+            },
+            0x75 => {
+                self.adc(self.mem[arg.wrapping_add(self.x) as usize]);
+                self.pc += 2;
+            },
+            0x85 => {
+                self.mem[arg as usize] = self.a;
+                self.pc += 2;
+            },
+            0x95 => {
+                self.mem[arg.wrapping_add(self.x) as usize] = self.a;
+                self.pc += 2;
+            },
+            _ => panic!("Opcode {:#04X} is not modelled", op_code)
+        }
+    }
+
+    fn adc(&mut self, val: u8)
+    {
+        let sum = self.a as u16 + val as u16 + (self.p & flag::C) as u16;
+        let result = sum as u8;
+
+        self.set_flag(flag::C, sum > 0xFF);
+        self.set_flag(flag::Z, result == 0);
+        self.set_flag(flag::V, (self.a ^ result) & (val ^ result) & 0x80 != 0);
+        self.set_flag(flag::N, result & 0x80 != 0);
+        self.a = result;
+    }
+
+    fn set_flag(&mut self, flag: u8, is_set: bool)
+    {
+        if is_set { self.p |= flag } else { self.p &= !flag }
+    }
+}
+
+fn instr() -> impl Strategy<Value = Instr>
+{
+    prop_oneof![
+        Just(Instr::Nop),
+        Just(Instr::Jmp),
+        any::<u8>().prop_map(Instr::AdcImm),
+        any::<u8>().prop_map(Instr::AdcZp),
+        any::<u8>().prop_map(Instr::AdcZpx),
+        any::<u8>().prop_map(Instr::StaZp),
+        any::<u8>().prop_map(Instr::StaZpx)
+    ]
+}
+
+proptest!
+{
+    #[test]
+    fn matches_reference(program in prop::collection::vec(instr(), 1..64), zero_page in prop::collection::vec(any::<u8>(), 0x100), x in 1..=0xFFu8)
+    {
+        let mut mem = vec![0; 0x10000];
+        mem[..0x100].copy_from_slice(&zero_page);
+        mem[..3].copy_from_slice(&[0x4C, PROGRAM_START as u8, (PROGRAM_START >> 8) as u8]);
+        let code = assemble(&program);
+        mem[PROGRAM_START as usize..][..code.len()].copy_from_slice(&code);
+
+        // There is no LDX yet, so X is seeded directly. Being non-zero, it
+        // makes ADC zp,X wrap around the zero page for high operands.
+        let mut reference = Reference { mem: mem.clone(), pc: 0, a: 0, x, p: 0 };
+        let mut cpu = CPU::new(Box::new(Flat(mem)));
+        let mut registers = cpu.get_registers();
+        registers.x = x;
+        cpu.set_registers(registers);
+
+        for _ in 0..=program.len() {
+            reference.step();
+            cpu.step().unwrap();
+
+            let regs = cpu.get_registers();
+            prop_assert_eq!(reference.pc, regs.pc);
+            prop_assert_eq!(reference.a, regs.a);
+            prop_assert_eq!(reference.x, regs.x);
+            prop_assert_eq!(reference.p, regs.ps.bits());
+        }
+
+        for addr in 0..=0xFFFF {
+            prop_assert_eq!(reference.mem[addr as usize], cpu.peek8(addr));
+        }
+    }
+}